@@ -0,0 +1,66 @@
+-- Schema as created before migrations were versioned, a no-op on databases from those builds.
+CREATE TABLE IF NOT EXISTS bot_state (
+    name TEXT PRIMARY KEY,
+    symbol TEXT,
+    timeframe TEXT,
+    strategy_name TEXT,
+    capital INTEGER,
+    bot_group TEXT,
+    is_not_active INTEGER,
+    wins INTEGER,
+    losses INTEGER,
+    log TEXT,
+    started_at TEXT,
+    last_scanned TEXT,
+    leverage INTEGER,
+    take_profit_ratio INTEGER,
+    stop_loss_ratio INTEGER,
+    is_trailing_stop_active bool,
+    trailing_stop_activation_point INTEGER,
+    in_pos INTEGER,
+    order_type TEXT,
+    order_created_at TEXT,
+    order_scanned_at TEXT,
+    order_quantity INTEGER,
+    order_capital INTEGER,
+    order_capital_with_leverage INTEGER,
+    order_entry_price INTEGER,
+    order_stop_loss INTEGER,
+    order_take_profit INTEGER,
+    order_fee INTEGER,
+    pnl INTEGER,
+    roe INTEGER,
+    mode TEXT NOT NULL DEFAULT 'Paper',
+    entry_order_id INTEGER NOT NULL DEFAULT 0,
+    stop_order_id INTEGER NOT NULL DEFAULT 0,
+    take_profit_order_id INTEGER NOT NULL DEFAULT 0,
+    strategy_params TEXT NOT NULL DEFAULT 'null'
+);
+
+CREATE TABLE IF NOT EXISTS bots (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    capital REAL NOT NULL,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    wins INTEGER NOT NULL DEFAULT 0,
+    losses INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS orders (
+    id TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    order_type TEXT NOT NULL,
+    bot_name TEXT NOT NULL,
+    entry_price INTEGER NOT NULL,
+    exit_price INTEGER NOT NULL,
+    quantity INTEGER NOT NULL,
+    pnl INTEGER NOT NULL,
+    roe INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    closed_at TEXT NOT NULL,
+    fee INTEGER NOT NULL,
+    leverage INTEGER NOT NULL,
+    entry_order_id INTEGER NOT NULL DEFAULT 0,
+    exit_order_id INTEGER NOT NULL DEFAULT 0
+);
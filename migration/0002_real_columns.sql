@@ -0,0 +1,86 @@
+-- Prices, amounts and ratios were declared INTEGER, SQLite can only change a column type by rebuilding the table.
+CREATE TABLE bot_state_new (
+    name TEXT PRIMARY KEY,
+    symbol TEXT,
+    timeframe TEXT,
+    strategy_name TEXT,
+    capital REAL,
+    bot_group TEXT,
+    is_not_active INTEGER,
+    wins INTEGER,
+    losses INTEGER,
+    log TEXT,
+    started_at TEXT,
+    last_scanned TEXT,
+    leverage REAL,
+    take_profit_ratio REAL,
+    stop_loss_ratio REAL,
+    is_trailing_stop_active INTEGER,
+    trailing_stop_activation_point REAL,
+    in_pos INTEGER,
+    order_type TEXT,
+    order_created_at TEXT,
+    order_scanned_at TEXT,
+    order_quantity REAL,
+    order_capital REAL,
+    order_capital_with_leverage REAL,
+    order_entry_price REAL,
+    order_stop_loss REAL,
+    order_take_profit REAL,
+    order_fee REAL,
+    pnl REAL,
+    roe REAL,
+    mode TEXT NOT NULL DEFAULT 'Paper',
+    entry_order_id INTEGER NOT NULL DEFAULT 0,
+    stop_order_id INTEGER NOT NULL DEFAULT 0,
+    take_profit_order_id INTEGER NOT NULL DEFAULT 0,
+    strategy_params TEXT NOT NULL DEFAULT 'null'
+);
+
+INSERT INTO bot_state_new (
+    name, symbol, timeframe, strategy_name, capital, bot_group, is_not_active, wins, losses, log,
+    started_at, last_scanned, leverage, take_profit_ratio, stop_loss_ratio, is_trailing_stop_active,
+    trailing_stop_activation_point, in_pos, order_type, order_created_at, order_scanned_at, order_quantity,
+    order_capital, order_capital_with_leverage, order_entry_price, order_stop_loss, order_take_profit, order_fee,
+    pnl, roe, mode, entry_order_id, stop_order_id, take_profit_order_id, strategy_params
+)
+SELECT
+    name, symbol, timeframe, strategy_name, capital, bot_group, is_not_active, wins, losses, log,
+    started_at, last_scanned, leverage, take_profit_ratio, stop_loss_ratio, is_trailing_stop_active,
+    trailing_stop_activation_point, in_pos, order_type, order_created_at, order_scanned_at, order_quantity,
+    order_capital, order_capital_with_leverage, order_entry_price, order_stop_loss, order_take_profit, order_fee,
+    pnl, roe, mode, entry_order_id, stop_order_id, take_profit_order_id, strategy_params
+FROM bot_state;
+
+DROP TABLE bot_state;
+ALTER TABLE bot_state_new RENAME TO bot_state;
+
+CREATE TABLE orders_new (
+    id TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    order_type TEXT NOT NULL,
+    bot_name TEXT NOT NULL,
+    entry_price REAL NOT NULL,
+    exit_price REAL NOT NULL,
+    quantity REAL NOT NULL,
+    pnl REAL NOT NULL,
+    roe REAL NOT NULL,
+    created_at TEXT NOT NULL,
+    closed_at TEXT NOT NULL,
+    fee REAL NOT NULL,
+    leverage REAL NOT NULL,
+    entry_order_id INTEGER NOT NULL DEFAULT 0,
+    exit_order_id INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO orders_new (
+    id, symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe,
+    created_at, closed_at, fee, leverage, entry_order_id, exit_order_id
+)
+SELECT
+    id, symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe,
+    created_at, closed_at, fee, leverage, entry_order_id, exit_order_id
+FROM orders;
+
+DROP TABLE orders;
+ALTER TABLE orders_new RENAME TO orders;
@@ -0,0 +1,7 @@
+-- Names of the default bots added from the config matrix, a deleted one is not added again.
+CREATE TABLE seeded_bots (
+    name TEXT PRIMARY KEY
+);
+
+-- Bots of a database written by an earlier build came from the matrix of that build.
+INSERT INTO seeded_bots (name) SELECT name FROM bot_state;
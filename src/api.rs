@@ -1,8 +1,13 @@
-use crate::enums::{OrderCommand, Symbol, Timeframe};
 use crate::models::bot::Bot;
-use crate::models::models::{BotStatistic, Container, Order, SharedVec, Statistic, StatisticResult, SystemInfo, TimeRange};
+use crate::models::bot_registry::BotRegistry;
+use crate::models::models::{BotStatistic, BotUpdate, Container, NewBot, Order, Statistic, StatisticResult, SystemInfo, TimeRange};
 use crate::tools::sort_bot_statistics;
-use crate::{api, tools};
+use crate::config;
+use crate::constants::MAX_LEVERAGE;
+use crate::enums::BotMode;
+use crate::strategy::strategy::find_strategy;
+use crate::supervisor::{Supervisor, TaskHealth};
+use crate::{api, symbols, tools};
 use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::routing::{get, put};
@@ -16,8 +21,8 @@ use rusqlite::params;
 use sysinfo::System;
 use tower_http::cors::{Any, CorsLayer};
 
-pub fn get_router(bots: Arc<SharedVec<Bot>>, container: Arc<Container>) -> Router {
-    let started_time = tools::get_date(3);
+pub fn get_router(bots: Arc<BotRegistry>, container: Arc<Container>, supervisor: Arc<Supervisor>) -> Router {
+    let started_time = tools::get_date(config::get().time_zone);
 
     let assets_router = Assets::router();
 
@@ -38,11 +43,14 @@ pub fn get_router(bots: Arc<SharedVec<Bot>>, container: Arc<Container>) -> Route
       .allow_methods(Any)
       .allow_headers(Any);
     assets_router
-      .route("/api/v1/bots", get(get_all_bot))
+      .route("/api/v1/bots", get(get_all_bot).post(create_bot))
       .route("/api/v1/bots/{id}/orders", get(get_orders_by_id))
-      .route("/api/v1/bots/{id}", get(get_bot_by_name))
+      .route("/api/v1/bots/{id}", get(get_bot_by_name).patch(update_bot).delete(delete_bot))
+      .route("/api/v1/bots/{id}/pause", put(pause_bot))
+      .route("/api/v1/bots/{id}/resume", put(resume_bot))
       .route("/api/v1/bots/reset", put(reset_bots))
       .route("/api/v1/system", get(api::get_system_usage))
+      .route("/api/v1/health", get(get_health))
       .route("/api/v1/bots/statistics", get(get_all_bot_statistics))
       .route("/api/v1/bots/{bot_name}/statistics", get(get_bot_statistics))
       .route("/api/v1/bots/{bot_name}/statistics/range", get(get_statistic_in_range))
@@ -51,6 +59,7 @@ pub fn get_router(bots: Arc<SharedVec<Bot>>, container: Arc<Container>) -> Route
       .layer(Extension(bots))
       .layer(Extension(started_time))
       .layer(Extension(container))
+      .layer(Extension(supervisor))
       .layer(cors)
       .fallback(fallback)
 }
@@ -73,69 +82,171 @@ pub async fn get_system_usage(Extension(started_time): Extension<DateTime<FixedO
     })
 }
 
-pub async fn get_bot_by_name(Path(id): Path<String>, Extension(bots): Extension<Arc<SharedVec<Bot>>>) -> Json<Bot> {
-    let bot_vec: &mut Vec<Bot>;
-
-    unsafe { bot_vec = &mut *bots.0.get(); }
-
-    let mut bot = Bot {
-        name: "".to_string(),
-        symbol: Symbol::SolUsdt,
-        timeframe: Timeframe::Min1,
-        strategy_name: "".to_string(),
-        strategy: None,
-        capital: 0.0,
-        group: "".to_string(),
-        is_not_active: false,
-        wins: 0,
-        losses: 0,
-        log: "".to_string(),
-        started_at: Default::default(),
-        last_scanned: Default::default(),
-        leverage: 0.0,
-        take_profit_ratio: 0.0,
-        stop_loss_ratio: 0.0,
-        is_trailing_stop_active: false,
-        trailing_stop_activation_point: 0.0,
-        in_pos: false,
-        order_type: OrderCommand::Long,
-        order_created_at: Default::default(),
-        order_scanned_at: Default::default(),
-        order_quantity: 0.0,
-        order_capital: 0.0,
-        order_capital_with_leverage: 0.0,
-        order_entry_price: 0.0,
-        order_stop_loss: 0.0,
-        order_take_profit: 0.0,
-        order_fee: 0.0,
-        pnl: 0.0,
-        roe: 0.0,
-    };
-
-    for b in bot_vec.iter() {
-        if b.name == id {
-            bot = b.clone();
+/// 503 while a manager loop is down or waiting to be restarted.
+pub async fn get_health(Extension(supervisor): Extension<Arc<Supervisor>>) -> (StatusCode, Json<Vec<TaskHealth>>) {
+    let status = if supervisor.is_healthy() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(supervisor.health()))
+}
+
+pub async fn get_bot_by_name(Path(id): Path<String>, Extension(bots): Extension<Arc<BotRegistry>>) -> Result<Json<Bot>, StatusCode> {
+    bots.get(&id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn create_bot(Extension(bots): Extension<Arc<BotRegistry>>, Extension(c): Extension<Arc<Container>>, Json(new_bot): Json<NewBot>) -> Result<(StatusCode, Json<Bot>), (StatusCode, String)> {
+    find_strategy(&new_bot.strategy_name, &new_bot.strategy_params).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    validate_settings(new_bot.capital, new_bot.leverage, new_bot.take_profit_ratio, new_bot.stop_loss_ratio, new_bot.trailing_stop_activation_point)
+      .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if symbols::get(&new_bot.symbol).is_none() {
+        return Err((StatusCode::BAD_REQUEST, format!("unknown symbol: {}", new_bot.symbol)));
+    }
+    if new_bot.mode == BotMode::Live && c.executor.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "live mode requires exchange credentials".to_string()));
+    }
+
+    let mut bot = Bot::new(
+        new_bot.timeframe,
+        new_bot.symbol,
+        new_bot.strategy_name,
+        new_bot.capital,
+        new_bot.leverage,
+        new_bot.take_profit_ratio,
+        new_bot.stop_loss_ratio,
+        new_bot.trailing_stop_activation_point,
+    );
+    bot.is_trailing_stop_active = new_bot.is_trailing_stop_active;
+    bot.mode = new_bot.mode;
+    bot.set_strategy_params(&new_bot.strategy_params).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    // a custom name lets the same strategy run on one pair with different params
+    if let Some(name) = new_bot.name {
+        if name.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "name must not be empty".to_string()));
         }
+        bot.name = name;
+    }
+
+    // saved before it goes live, a failed write leaves nothing behind
+    if bots.find(&bot.name).is_some() {
+        return Err((StatusCode::CONFLICT, format!("bot {} already exists", bot.name)));
     }
+    persist(&c, &bot).await?;
 
-    Json(bot)
+    let saved = bot.clone();
+    if !bots.insert(bot) {
+        return Err((StatusCode::CONFLICT, format!("bot {} already exists", saved.name)));
+    }
+    if let Some(stream) = c.market_stream.as_ref() {
+        stream.subscribe(saved.symbol, saved.timeframe);
+    }
+
+    Ok((StatusCode::CREATED, Json(saved)))
 }
 
-pub async fn get_all_bot(Extension(bots): Extension<Arc<SharedVec<Bot>>>, Query(params): Query<HashMap<String, String>>) -> Json<Vec<Bot>> {
-    let bot_vec: &mut Vec<Bot>;
+pub async fn update_bot(Path(id): Path<String>, Extension(bots): Extension<Arc<BotRegistry>>, Extension(c): Extension<Arc<Container>>, Json(update): Json<BotUpdate>) -> Result<Json<Bot>, (StatusCode, String)> {
+    let handle = bots.find(&id).ok_or((StatusCode::NOT_FOUND, format!("bot {} not found", id)))?;
+    let mut bot = handle.lock().await;
 
-    if let Some(timeframe) = params.get("timeframe") {
-        if !timeframe.eq("") && !timeframe.to_lowercase().eq("all") {
-        }
+    // the open position was sized with the current capital and leverage
+    if bot.in_pos && (update.capital.is_some() || update.leverage.is_some()) {
+        return Err((StatusCode::CONFLICT, "capital and leverage can't change while in position".to_string()));
     }
 
-    let timeframe = params.get("timeframe").map(|s| s.to_lowercase());
+    validate_settings(
+        update.capital.unwrap_or(bot.capital),
+        update.leverage.unwrap_or(bot.leverage),
+        update.take_profit_ratio.unwrap_or(bot.take_profit_ratio),
+        update.stop_loss_ratio.unwrap_or(bot.stop_loss_ratio),
+        update.trailing_stop_activation_point.unwrap_or(bot.trailing_stop_activation_point),
+    ).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    // the running bot only changes once the new settings are saved
+    let mut updated = bot.clone();
+    if let Some(params) = &update.strategy_params {
+        updated.set_strategy_params(params).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    if let Some(capital) = update.capital { updated.capital = capital; }
+    if let Some(leverage) = update.leverage { updated.leverage = leverage; }
+    if let Some(ratio) = update.take_profit_ratio { updated.take_profit_ratio = ratio; }
+    if let Some(ratio) = update.stop_loss_ratio { updated.stop_loss_ratio = ratio; }
+    if let Some(active) = update.is_trailing_stop_active { updated.is_trailing_stop_active = active; }
+    if let Some(point) = update.trailing_stop_activation_point { updated.trailing_stop_activation_point = point; }
+
+    persist(&c, &updated).await?;
+    *bot = updated.clone();
+    Ok(Json(updated))
+}
+
+pub async fn delete_bot(Path(id): Path<String>, Extension(bots): Extension<Arc<BotRegistry>>, Extension(c): Extension<Arc<Container>>) -> Result<StatusCode, (StatusCode, String)> {
+    let handle = bots.find(&id).ok_or((StatusCode::NOT_FOUND, format!("bot {} not found", id)))?;
+    let mut bot = handle.lock().await;
+    if bot.in_pos {
+        return Err((StatusCode::CONFLICT, "bot is in position, pause it and wait for the exit".to_string()));
+    }
+
+    c.repository.delete_bot_state(&id).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // managers that already hold the handle see the flag once they get the lock
+    bot.deleted = true;
+    bots.remove(&id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn pause_bot(Path(id): Path<String>, Extension(bots): Extension<Arc<BotRegistry>>, Extension(c): Extension<Arc<Container>>) -> Result<Json<Bot>, (StatusCode, String)> {
+    set_active(&id, false, &bots, &c).await
+}
+
+pub async fn resume_bot(Path(id): Path<String>, Extension(bots): Extension<Arc<BotRegistry>>, Extension(c): Extension<Arc<Container>>) -> Result<Json<Bot>, (StatusCode, String)> {
+    set_active(&id, true, &bots, &c).await
+}
+
+/// A paused bot opens no new positions, an open one is still managed until it exits.
+async fn set_active(id: &str, active: bool, bots: &BotRegistry, c: &Container) -> Result<Json<Bot>, (StatusCode, String)> {
+    let handle = bots.find(id).ok_or((StatusCode::NOT_FOUND, format!("bot {} not found", id)))?;
+    let mut bot = handle.lock().await;
+    if active && bot.strategy.is_none() {
+        return Err((StatusCode::CONFLICT, format!("bot {} has no valid strategy, fix its params first", id)));
+    }
+    let mut updated = bot.clone();
+    updated.is_not_active = !active;
+
+    persist(c, &updated).await?;
+    *bot = updated.clone();
+    Ok(Json(updated))
+}
+
+async fn persist(c: &Container, bot: &Bot) -> Result<(), (StatusCode, String)> {
+    c.repository
+      .save_bot_state(vec![bot.clone()])
+      .await
+      .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn validate_settings(capital: f64, leverage: f64, take_profit_ratio: f64, stop_loss_ratio: f64, trailing_stop_activation_point: f64) -> Result<(), String> {
+    let min_capital = config::get().risk.min_capital_to_stop;
+    if !capital.is_finite() || capital <= min_capital {
+        return Err(format!("capital must be above {}", min_capital));
+    }
+    if !(1.0..=MAX_LEVERAGE).contains(&leverage) {
+        return Err(format!("leverage must be between 1 and {}", MAX_LEVERAGE));
+    }
+    // ratios are percent of the entry price
+    if !(take_profit_ratio > 0.0 && take_profit_ratio < 100.0) {
+        return Err("take_profit_ratio must be between 0 and 100".to_string());
+    }
+    if !(stop_loss_ratio > 0.0 && stop_loss_ratio < 100.0 / leverage) {
+        return Err(format!("stop_loss_ratio must be between 0 and {:.2} at leverage {}", 100.0 / leverage, leverage));
+    }
+    if !(0.0..100.0).contains(&trailing_stop_activation_point) {
+        return Err("trailing_stop_activation_point must be between 0 and 100".to_string());
+    }
+    Ok(())
+}
 
-    unsafe { bot_vec = &mut *bots.0.get(); }
+pub async fn get_all_bot(Extension(bots): Extension<Arc<BotRegistry>>, Query(params): Query<HashMap<String, String>>) -> Json<Vec<Bot>> {
+    let timeframe = params.get("timeframe").map(|s| s.to_lowercase());
 
     // Filter bots based on timeframe if provided
-    let mut filtered: Vec<Bot> = bot_vec
-        .iter()
+    let mut filtered: Vec<Bot> = bots
+        .snapshot()
+        .into_iter()
         .filter(|bot| match &timeframe {
             Some(tf) =>{
                 let tf_lower = tf.to_lowercase();
@@ -143,7 +254,6 @@ pub async fn get_all_bot(Extension(bots): Extension<Arc<SharedVec<Bot>>>, Query(
             }
             None => true,
         })
-        .cloned()
         .collect();
 
 
@@ -151,25 +261,17 @@ pub async fn get_all_bot(Extension(bots): Extension<Arc<SharedVec<Bot>>>, Query(
     Json(filtered)
 }
 
-pub async fn save_bot_states(Extension(bots): Extension<Arc<SharedVec<Bot>>>, Extension(c): Extension<Arc<Container>>) {
-    let mut vec = Vec::new();
-    unsafe {
-        let bots = &mut *bots.0.get();
-
-        for bot in bots.iter() {
-            vec.push(bot.clone());
-        }
-    }
-    c.repository.save_bot_state(vec).expect("problem to save");
+pub async fn save_bot_states(Extension(bots): Extension<Arc<BotRegistry>>, Extension(c): Extension<Arc<Container>>) -> Result<(), (StatusCode, String)> {
+    c.repository.save_bot_state(bots.snapshot()).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
 
-pub async fn get_bot_states(Extension(c): Extension<Arc<Container>>) -> Json<Vec<Bot>> {
-    let res = c.repository.get_bot_state().expect("error to get bot state");
-    Json(res)
+pub async fn get_bot_states(Extension(c): Extension<Arc<Container>>) -> Result<Json<Vec<Bot>>, (StatusCode, String)> {
+    let res = c.repository.get_bot_state().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(res))
 }
 
-pub async fn get_orders_by_id(Path(id): Path<String>, Extension(container): Extension<Arc<Container>>) -> Json<Vec<Order>> {
-    let orders = container.repository.get_order_by_bot_name(id).unwrap();
+pub async fn get_orders_by_id(Path(id): Path<String>, Extension(container): Extension<Arc<Container>>) -> Result<Json<Vec<Order>>, (StatusCode, String)> {
+    let orders = container.repository.get_order_by_bot_name(id).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     // orders.reverse();
 
     // let mut orders = Vec::new();
@@ -189,20 +291,17 @@ pub async fn get_orders_by_id(Path(id): Path<String>, Extension(container): Exte
     //
 
 
-    Json(orders)
+    Ok(Json(orders))
 }
 
-pub async fn reset_bots(Extension(bots): Extension<Arc<SharedVec<Bot>>>) {
-    unsafe {
-        let bots = &mut *bots.0.get();
-        for b in bots.iter_mut() {
-            b.reset();
-        }
+pub async fn reset_bots(Extension(bots): Extension<Arc<BotRegistry>>) {
+    for b in bots.handles() {
+        b.lock().await.reset();
     }
 }
 
-pub async fn get_all_bot_statistics(Extension(c): Extension<Arc<Container>>) -> Json<Statistic> {
-    let bots = c.repository.get_all_bots().unwrap();
+pub async fn get_all_bot_statistics(Extension(c): Extension<Arc<Container>>) -> Result<Json<Statistic>, (StatusCode, String)> {
+    let bots = c.repository.get_all_bots().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let mut hm = HashMap::new();
 
     for b in bots.into_iter() {
@@ -225,16 +324,16 @@ pub async fn get_all_bot_statistics(Extension(c): Extension<Arc<Container>>) ->
     }
     sort_bot_statistics(&mut bot_statistics);
 
-    Json(Statistic {
+    Ok(Json(Statistic {
         bot_statistics,
-    })
+    }))
 }
 
-pub async fn get_bot_statistics(Path(bot_name): Path<String>, Extension(c): Extension<Arc<Container>>) -> Json<Statistic> {
-    let vec = c.repository.get_bot(bot_name).unwrap();
+pub async fn get_bot_statistics(Path(bot_name): Path<String>, Extension(c): Extension<Arc<Container>>) -> Result<Json<Statistic>, (StatusCode, String)> {
+    let vec = c.repository.get_bot(bot_name).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let mut bot_statistics = Vec::with_capacity(vec.len());
     if vec.is_empty() {
-        return Json(Statistic { bot_statistics });
+        return Ok(Json(Statistic { bot_statistics }));
     }
 
     let (win_days, lose_days, capital) = get_win_loss_capital(&vec).await;
@@ -250,31 +349,32 @@ pub async fn get_bot_statistics(Path(bot_name): Path<String>, Extension(c): Exte
 
     sort_bot_statistics(&mut bot_statistics);
 
-    Json(Statistic {
+    Ok(Json(Statistic {
         bot_statistics,
-    })
+    }))
 }
 
-pub async fn get_statistic_in_range(Path(bot_name): Path<String>, Query(range): Query<TimeRange>, Extension(c): Extension<Arc<Container>>) -> Json<Vec<Order>> {
-    let start = tools::parse_time(&range.start_time);
-    let end = tools::parse_time(&range.end_time);
-    let vec = c.repository.get_orders_in_range(bot_name, start, end).unwrap();
+pub async fn get_statistic_in_range(Path(bot_name): Path<String>, Query(range): Query<TimeRange>, Extension(c): Extension<Arc<Container>>) -> Result<Json<Vec<Order>>, (StatusCode, String)> {
+    let start = tools::parse_time(&range.start_time).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let end = tools::parse_time(&range.end_time).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let vec = c.repository.get_orders_in_range(bot_name, start, end).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Json(vec)
+    Ok(Json(vec))
 }
 
 async fn get_win_loss_capital(statistics: &Vec<StatisticResult>) -> (u16, u16, f64) {
+    let start_capital = config::get().risk.capital;
     let mut win_days = 0;
     let mut lose_days = 0;
     let mut capital = 0.0;
     for res in statistics.iter() {
-        if res.capital > 100.0 {
+        if res.capital > start_capital {
             win_days += 1;
-        } else if res.capital < 100.0 {
+        } else if res.capital < start_capital {
             lose_days += 1;
         }
 
-        capital += res.capital - 100.0;
+        capital += res.capital - start_capital;
     }
     (win_days, lose_days, capital)
 }
@@ -330,4 +430,169 @@ impl Assets {
 
         router
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{Symbol, Timeframe};
+    use crate::repository::Repository;
+    use crate::symbols::SymbolInfo;
+    use serde_json::{json, Value};
+
+    fn db_path(test: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("traderrs_api_{}_{}.sqlite", test, std::process::id()))
+    }
+
+    fn container(test: &str) -> Arc<Container> {
+        let path = db_path(test);
+        let _ = std::fs::remove_file(&path);
+        Arc::new(Container { repository: Repository::new(path).unwrap(), executor: None, market_stream: None })
+    }
+
+    fn new_bot() -> NewBot {
+        // only pairs with known exchange rules are accepted, named like SOLUSDT bots
+        let symbol = Symbol::new("SOLUSDC");
+        symbols::register_missing([SymbolInfo { symbol, tick_size: 0.01, step_size: 0.01, min_notional: 5.0 }]);
+        NewBot {
+            symbol,
+            timeframe: Timeframe::Min5,
+            strategy_name: "EmaMacd".to_string(),
+            capital: 100.0,
+            leverage: 10.0,
+            take_profit_ratio: 0.8,
+            stop_loss_ratio: 0.4,
+            trailing_stop_activation_point: 0.1,
+            is_trailing_stop_active: true,
+            mode: BotMode::Paper,
+            name: None,
+            strategy_params: Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn bot_lifecycle_is_persisted() {
+        let c = container("lifecycle");
+        let bots = Arc::new(BotRegistry::default());
+
+        let (status, Json(bot)) = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(new_bot())).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(c.repository.get_bot_state().await.unwrap().len(), 1);
+
+        let err = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(new_bot())).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::CONFLICT);
+
+        let update = BotUpdate { leverage: Some(5.0), stop_loss_ratio: Some(1.0), ..Default::default() };
+        let Json(updated) = update_bot(Path(bot.name.clone()), Extension(bots.clone()), Extension(c.clone()), Json(update)).await.unwrap();
+        assert_eq!(updated.leverage, 5.0);
+        let Json(paused) = pause_bot(Path(bot.name.clone()), Extension(bots.clone()), Extension(c.clone())).await.unwrap();
+        assert!(paused.is_not_active);
+
+        let saved = &c.repository.get_bot_state().await.unwrap()[0];
+        assert_eq!((saved.leverage, saved.stop_loss_ratio, saved.is_not_active), (5.0, 1.0, true));
+
+        // a manager tick that listed the handles before the delete
+        let stale = bots.find(&bot.name).unwrap();
+        let status = delete_bot(Path(bot.name.clone()), Extension(bots.clone()), Extension(c.clone())).await.unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(bots.is_empty());
+        assert!(c.repository.get_bot_state().await.unwrap().is_empty());
+
+        let stale = stale.lock().await.clone();
+        assert!(stale.deleted && stale.is_not_allowed_for_scanning(&stale.last_scanned));
+        c.repository.save_bot_state(vec![stale]).await.unwrap();
+        assert!(c.repository.get_bot_state().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_writes_leave_the_running_bots_alone() {
+        let c = container("failed_writes");
+        let bots = Arc::new(BotRegistry::default());
+        let (_, Json(bot)) = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(new_bot())).await.unwrap();
+        rusqlite::Connection::open(db_path("failed_writes")).unwrap().execute_batch("DROP TABLE bot_state").unwrap();
+
+        let update = BotUpdate { leverage: Some(5.0), ..Default::default() };
+        let err = update_bot(Path(bot.name.clone()), Extension(bots.clone()), Extension(c.clone()), Json(update)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::INTERNAL_SERVER_ERROR);
+        let err = pause_bot(Path(bot.name.clone()), Extension(bots.clone()), Extension(c.clone())).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::INTERNAL_SERVER_ERROR);
+        let running = bots.get(&bot.name).unwrap();
+        assert_eq!((running.leverage, running.is_not_active), (10.0, false));
+
+        let other = NewBot { name: Some("other".to_string()), ..new_bot() };
+        let err = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(other)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(bots.len(), 1);
+
+        // reads and saves answer with an error instead of panicking
+        let err = get_bot_states(Extension(c.clone())).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::INTERNAL_SERVER_ERROR);
+        let err = save_bot_states(Extension(bots.clone()), Extension(c.clone())).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn invalid_settings_are_rejected() {
+        let c = container("invalid");
+        let bots = Arc::new(BotRegistry::default());
+
+        let mut bot = new_bot();
+        bot.strategy_name = "Nope".to_string();
+        let err = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(bot)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        let mut bot = new_bot();
+        bot.symbol = Symbol::new("NOPEUSDT");
+        let err = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(bot)).await.unwrap_err();
+        assert_eq!(err, (StatusCode::BAD_REQUEST, "unknown symbol: NOPEUSDT".to_string()));
+
+        let mut bot = new_bot();
+        bot.stop_loss_ratio = 15.0;
+        let err = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(bot)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        let (_, Json(created)) = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(new_bot())).await.unwrap();
+        bots.find(&created.name).unwrap().lock().await.in_pos = true;
+        let update = BotUpdate { capital: Some(200.0), ..Default::default() };
+        let err = update_bot(Path(created.name.clone()), Extension(bots.clone()), Extension(c.clone()), Json(update)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::CONFLICT);
+        let err = delete_bot(Path(created.name), Extension(bots), Extension(c)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn unparsable_time_range_is_a_bad_request() {
+        let c = container("time_range");
+        let range = TimeRange { start_time: "yesterday".to_string(), end_time: "2025-01-06T10:00".to_string() };
+        let err = get_statistic_in_range(Path("EmaMacd_5m_Sol".to_string()), Query(range), Extension(c.clone())).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        let range = TimeRange { start_time: "2025-01-06T00:00".to_string(), end_time: "2025-01-06T10:00".to_string() };
+        let Json(orders) = get_statistic_in_range(Path("EmaMacd_5m_Sol".to_string()), Query(range), Extension(c)).await.unwrap();
+        assert!(orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn same_strategy_runs_with_different_params() {
+        let c = container("params");
+        let bots = Arc::new(BotRegistry::default());
+
+        let (_, Json(default)) = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(new_bot())).await.unwrap();
+        let mut tuned = new_bot();
+        tuned.name = Some("EmaMacd_5m_Sol_ema50".to_string());
+        tuned.strategy_params = json!({"trend_ema": 50});
+        let (_, Json(tuned)) = create_bot(Extension(bots.clone()), Extension(c.clone()), Json(tuned)).await.unwrap();
+        assert_eq!((default.name.as_str(), tuned.name.as_str()), ("EmaMacd_5m_Sol", "EmaMacd_5m_Sol_ema50"));
+
+        let mut saved = c.repository.get_bot_state().await.unwrap();
+        saved.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(saved[0].strategy_params["trend_ema"], json!(200));
+        assert_eq!(saved[1].strategy_params["trend_ema"], json!(50));
+        assert_eq!(saved[1].strategy.as_ref().unwrap().params()["trend_ema"], json!(50));
+
+        let update = BotUpdate { strategy_params: Some(json!({"trend_ema": 0})), ..Default::default() };
+        let err = update_bot(Path("EmaMacd_5m_Sol_ema50".to_string()), Extension(bots.clone()), Extension(c.clone()), Json(update)).await.unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert_eq!(bots.get("EmaMacd_5m_Sol_ema50").unwrap().strategy_params["trend_ema"], json!(50));
+    }
+
+}
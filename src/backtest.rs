@@ -0,0 +1,306 @@
+use crate::config;
+use crate::constants::CANDLES_WINDOW;
+use crate::enums::OrderCommand;
+use crate::models::bot::Bot;
+use crate::models::models::{Candle, Order, PriceRange, StrategyContainer};
+use crate::tools;
+use crate::tools::{get_exit_price, shift_stop_loss, update_pnl_and_roe};
+use chrono::{DateTime, FixedOffset};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EquityPoint {
+    pub time: DateTime<FixedOffset>,
+    pub equity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BacktestResult {
+    pub bot_name: String,
+    pub initial_capital: f64,
+    pub final_capital: f64,
+    pub orders: Vec<Order>,
+    pub equity_curve: Vec<EquityPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BacktestReport {
+    pub bot_name: String,
+    pub initial_capital: f64,
+    pub final_capital: f64,
+    pub net_pnl: f64,
+    pub trade_count: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    pub max_drawdown: f64,
+    pub max_drawdown_pct: f64,
+    pub fees: f64,
+}
+
+impl BacktestResult {
+    pub fn report(&self) -> BacktestReport {
+        let wins = self.orders.iter().filter(|o| o.pnl > 0.0).count();
+        let trade_count = self.orders.len();
+        let gross_profit: f64 = self.orders.iter().filter(|o| o.pnl > 0.0).map(|o| o.pnl).sum();
+        let gross_loss: f64 = self.orders.iter().filter(|o| o.pnl < 0.0).map(|o| -o.pnl).sum();
+        // an empty sum is -0.0, which prints as "-0.00"
+        let fees = self.orders.iter().map(|o| o.fee).sum::<f64>() + 0.0;
+
+        let profit_factor = if gross_loss > 0.0 {
+            gross_profit / gross_loss
+        } else if gross_profit > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+
+        let mut peak = self.initial_capital;
+        let mut max_drawdown = 0.0;
+        let mut max_drawdown_pct = 0.0;
+        for point in self.equity_curve.iter() {
+            peak = peak.max(point.equity);
+            let drawdown = peak - point.equity;
+            if drawdown > max_drawdown {
+                max_drawdown = drawdown;
+                max_drawdown_pct = if peak > 0.0 { drawdown / peak * 100.0 } else { 0.0 };
+            }
+        }
+
+        BacktestReport {
+            bot_name: self.bot_name.clone(),
+            initial_capital: self.initial_capital,
+            final_capital: self.final_capital,
+            net_pnl: self.final_capital - self.initial_capital,
+            trade_count,
+            wins,
+            losses: trade_count - wins,
+            win_rate: if trade_count > 0 { wins as f64 / trade_count as f64 * 100.0 } else { 0.0 },
+            profit_factor,
+            max_drawdown,
+            max_drawdown_pct,
+            fees,
+        }
+    }
+}
+
+/// Walks a candle series bar by bar and trades a single bot on it.
+///
+/// Every bar first runs the exit checks the `PositionManager` does against the bar high and low,
+/// then, if the bot is flat, the strategy scan the `EntryManager` does at the bar close. Fees, stop-loss, take-profit and
+/// trailing stop all go through the same `Bot` and `calculator` code as live trading.
+pub struct Backtester {
+    bot: Bot,
+    candles: Vec<Candle>,
+    trade_from: u64,
+    slippage: f64,
+}
+
+impl Backtester {
+    pub fn new(bot: Bot, mut candles: Vec<Candle>) -> Self {
+        candles.sort_by_key(|c| c.open_time);
+        Self { bot, candles, trade_from: 0, slippage: config::get().risk.exit_slippage }
+    }
+
+    /// Slippage on stop-loss and take-profit fills, in percent.
+    pub fn slippage(mut self, slippage_pct: f64) -> Self {
+        self.slippage = slippage_pct;
+        self
+    }
+
+    /// Bars opening before `open_time` only warm up the indicators.
+    pub fn trade_from(mut self, open_time: u64) -> Self {
+        self.trade_from = open_time;
+        self
+    }
+
+    pub fn run(mut self) -> BacktestResult {
+        let initial_capital = self.bot.capital;
+        let duration = self.bot.timeframe.duration_ms();
+        let mut orders = Vec::new();
+        let mut equity_curve = Vec::with_capacity(self.candles.len());
+        let mut sc = StrategyContainer::new();
+        if let Some(strategy) = &self.bot.strategy {
+            sc.require(self.bot.timeframe, self.bot.symbol, &strategy.indicators());
+        }
+
+        for i in 0..self.candles.len() {
+            if self.candles[i].open_time < self.trade_from {
+                continue;
+            }
+            let candle = &self.candles[i];
+            let close = candle.close;
+            let range = PriceRange { high: candle.high, low: candle.low, last: close };
+            let now = tools::date_from_millis(candle.open_time + duration, config::get().time_zone);
+
+            if self.bot.in_pos {
+                if let Some(order) = self.check_exit(&range, now) {
+                    orders.push(order);
+                }
+            }
+
+            if !self.bot.in_pos && !self.bot.is_not_active {
+                let start = (i + 1).saturating_sub(CANDLES_WINDOW);
+                sc.insert_candles(self.bot.timeframe, self.bot.symbol, self.candles[start..=i].to_vec());
+                sc.calculate_all();
+
+                let (command, info) = self.bot.run_strategy(&sc);
+                debug!("backtest {}: {:?}, {}", self.bot.name, command, info);
+
+                if command != OrderCommand::Wait {
+                    if let Err(e) = self.bot.open_position_at(&command, close, now) {
+                        debug!("backtest {} can't open position: {}", self.bot.name, e);
+                    }
+                }
+                self.bot.last_scanned = now;
+                sc.reset();
+            }
+
+            equity_curve.push(EquityPoint {
+                time: now,
+                equity: self.bot.capital + self.bot.order_capital + self.bot.pnl,
+            });
+        }
+
+        // realize whatever is still open so the order list covers the whole run
+        if self.bot.in_pos {
+            if let Some(last) = self.candles.last() {
+                let now = tools::date_from_millis(last.open_time + duration, config::get().time_zone);
+                match self.bot.close_position(last.close, now) {
+                    Ok(order) => orders.push(order),
+                    Err(e) => warn!("backtest {} can't close final position: {}", self.bot.name, e),
+                }
+                if let Some(point) = equity_curve.last_mut() {
+                    point.equity = self.bot.capital;
+                }
+            }
+        }
+
+        BacktestResult {
+            bot_name: self.bot.name.clone(),
+            initial_capital,
+            final_capital: self.bot.capital,
+            orders,
+            equity_curve,
+        }
+    }
+
+    fn check_exit(&mut self, range: &PriceRange, now: DateTime<FixedOffset>) -> Option<Order> {
+        if let Some(exit_price) = get_exit_price(range, &self.bot, self.slippage) {
+            return self.bot.close_position(exit_price, now).ok();
+        }
+
+        update_pnl_and_roe(&mut self.bot, range.last);
+        shift_stop_loss(&mut self.bot);
+        self.bot.last_scanned = now;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{Symbol, Timeframe};
+    use crate::strategy::strategy::{find_strategy, STRATEGIES};
+    use serde_json::json;
+
+    // 2025-01-06 00:00 UTC
+    const START_MS: u64 = 1_736_121_600_000;
+
+    fn order(pnl: f64) -> Order {
+        Order { pnl, fee: 0.5, ..Order::dummy() }
+    }
+
+    fn point(equity: f64) -> EquityPoint {
+        EquityPoint { time: tools::date_from_millis(START_MS, 0), equity }
+    }
+
+    // 40 one minute bars falling from 100 to 96, then 40 rising to 104
+    fn v_series() -> Vec<Candle> {
+        let closes = (1..=40).map(|i| 100.0 - 0.1 * i as f64).chain((1..=40).map(|i| 96.0 + 0.2 * i as f64));
+        let mut open = 100.0;
+        closes
+            .enumerate()
+            .map(|(i, close)| {
+                let open_time = START_MS + i as u64 * 60_000;
+                let candle = Candle {
+                    close: (close * 100.0_f64).round() / 100.0,
+                    open,
+                    high: open.max(close),
+                    low: open.min(close),
+                    open_time,
+                    close_time: open_time + 59_999,
+                    volume: 1.0,
+                    is_closed: true,
+                };
+                open = candle.close;
+                candle
+            })
+            .collect()
+    }
+
+    #[test]
+    fn report_from_known_trades() {
+        let result = BacktestResult {
+            bot_name: "known".to_string(),
+            initial_capital: 100.0,
+            final_capital: 101.0,
+            orders: vec![order(3.0), order(-1.0), order(2.0), order(-3.0)],
+            equity_curve: [103.0, 102.0, 104.0, 101.0, 102.0].into_iter().map(point).collect(),
+        };
+
+        let report = result.report();
+        assert_eq!((report.trade_count, report.wins, report.losses), (4, 2, 2));
+        assert_eq!((report.win_rate, report.net_pnl, report.fees), (50.0, 1.0, 2.0));
+        assert_eq!(report.profit_factor, 5.0 / 4.0);
+        // the peak of 104 falls to 101
+        assert_eq!((report.max_drawdown, report.max_drawdown_pct), (3.0, 3.0 / 104.0 * 100.0));
+
+        let winners_only = BacktestResult { orders: vec![order(1.0)], equity_curve: Vec::new(), ..result };
+        assert_eq!(winners_only.report().profit_factor, f64::INFINITY);
+        assert_eq!(winners_only.report().max_drawdown, 0.0);
+
+        let no_trades = BacktestResult { orders: Vec::new(), ..winners_only };
+        assert_eq!(format!("{:.2}", no_trades.report().fees), "0.00");
+    }
+
+    #[test]
+    fn run_trades_a_known_series() {
+        let candles = v_series();
+
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("BACKTESTUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.is_trailing_stop_active = false;
+        let strategy = find_strategy("EmaMacd", &json!({"trend_ema": 5, "macd_fast": 3, "macd_slow": 6, "macd_signal": 3})).unwrap();
+        bot.strategy_params = strategy.params();
+        bot.strategy = Some(strategy);
+
+        let result = Backtester::new(bot, candles).slippage(0.0).trade_from(START_MS + 10 * 60_000).run();
+        assert_eq!(result.equity_curve.len(), 70);
+
+        // MACD turns positive on the bar closing at 96.60, the take-profit 0.8% above is hit four bars later
+        assert_eq!(result.orders.len(), 1, "{:?}", result.orders);
+        let order = &result.orders[0];
+        assert_eq!((order.order_type, order.entry_price, order.exit_price), (OrderCommand::Long, 96.6, 96.6 * 1.008));
+        assert_eq!(order.closed_at - order.created_at, chrono::Duration::minutes(4));
+
+        // both fees are in the order, the pnl is what the position made
+        let report = result.report();
+        assert!((result.final_capital - (100.0 + order.pnl - order.fee)).abs() < 1e-9);
+        assert_eq!(result.equity_curve.last().unwrap().equity, result.final_capital);
+        assert_eq!((report.wins, report.losses, report.profit_factor), (1, 0, f64::INFINITY));
+        // the only dip is the entry fee, 0.04% of the capital
+        assert!((report.max_drawdown - 0.04).abs() < 1e-9, "{}", report.max_drawdown);
+    }
+
+    #[test]
+    fn every_strategy_runs_from_the_first_bar() {
+        // the first scans see windows too short for any indicator
+        for name in STRATEGIES {
+            let bot = Bot::new(Timeframe::Min1, Symbol::new("BACKTESTUSDT"), name.to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+            let result = Backtester::new(bot, v_series()).run();
+            assert_eq!(result.equity_curve.len(), 80, "{}", name);
+        }
+    }
+}
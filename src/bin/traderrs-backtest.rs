@@ -0,0 +1,202 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::Value;
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::exit;
+use traderrs::backtest::{BacktestReport, Backtester};
+use traderrs::config;
+use traderrs::config::{Config, DEFAULT_CONFIG_PATH};
+use traderrs::connector::MarketDataSource;
+use traderrs::constants::CANDLES_WINDOW;
+use traderrs::enums::{Symbol, Timeframe};
+use traderrs::logger::init_logger;
+use traderrs::models::bot::Bot;
+use traderrs::replay_connector::ReplayConnector;
+use traderrs::strategy::strategy::find_strategy;
+use traderrs::symbols;
+
+const USAGE: &str = "usage: traderrs-backtest --strategy <name> --symbol <SOLUSDT> --timeframe <15m> --file <klines.csv|json>
+         [--from <YYYY-MM-DD[THH:MM]>] [--to <YYYY-MM-DD[THH:MM]>] [--out <report.json>]
+         [--params <json>] [--capital <n>] [--leverage <n>] [--take-profit <n>] [--stop-loss <n>] [--trailing <n>] [--slippage <n>]
+without --from trading starts at the first bar that completes a full candle window
+risk defaults and symbol rules come from $TRADERRS_CONFIG (traderrs.toml), like the live service";
+
+struct Args {
+    strategy: String,
+    params: Value,
+    symbol: Symbol,
+    timeframe: Timeframe,
+    file: PathBuf,
+    from: Option<u64>,
+    to: Option<u64>,
+    out: Option<PathBuf>,
+    capital: f64,
+    leverage: f64,
+    take_profit_ratio: f64,
+    stop_loss_ratio: f64,
+    trailing_stop_activation_point: f64,
+    slippage: f64,
+}
+
+#[tokio::main]
+async fn main() {
+    // the same config and exchange rules as live trading, so fills are rounded the same way
+    let config_path = env::var("TRADERRS_CONFIG").unwrap_or(DEFAULT_CONFIG_PATH.to_string());
+    let config = match Config::load(&PathBuf::from(&config_path)) {
+        Ok(config) => config::init(config),
+        Err(e) => {
+            eprintln!("failed to load {}: {}", config_path, e);
+            exit(1);
+        }
+    };
+    init_logger();
+    symbols::load(config).await;
+
+    let args = match parse_args(env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(e) = run(args).await {
+        eprintln!("backtest failed: {}", e);
+        exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn Error + Send + Sync>> {
+    find_strategy(&args.strategy, &args.params)?;
+    if symbols::get(&args.symbol).is_none() {
+        eprintln!("no exchange rules for {}, quantities and prices are not rounded", args.symbol);
+    }
+
+    let mut replay = ReplayConnector::new(0);
+    replay.load_file(args.symbol, args.timeframe, &args.file)?;
+    let (_, last) = replay.time_range().ok_or("candle file is empty")?;
+
+    // everything up to the end of the range, the window before `from` only warms up indicators
+    replay.set_time(args.to.unwrap_or(last + args.timeframe.duration_ms()));
+    let mut candles = replay.get_candles(args.symbol, args.timeframe, i32::MAX).await?;
+    // the first traded bar completes the window
+    let trade_from = match args.from {
+        Some(from) => {
+            let warm_up = candles.iter().filter(|c| c.open_time < from).count().saturating_sub(CANDLES_WINDOW - 1);
+            candles.drain(..warm_up);
+            from
+        }
+        None => candles
+            .get(CANDLES_WINDOW - 1)
+            .map(|c| c.open_time)
+            .ok_or(format!("{} candles, at least {} are needed to warm up the indicators", candles.len(), CANDLES_WINDOW))?,
+    };
+
+    let mut bot = Bot::new(
+        args.timeframe,
+        args.symbol,
+        args.strategy.clone(),
+        args.capital,
+        args.leverage,
+        args.take_profit_ratio,
+        args.stop_loss_ratio,
+        args.trailing_stop_activation_point,
+    );
+    bot.set_strategy_params(&args.params)?;
+
+    let result = Backtester::new(bot, candles)
+        .trade_from(trade_from)
+        .slippage(args.slippage)
+        .run();
+
+    let report = result.report();
+    print_report(&report);
+
+    if let Some(out) = args.out {
+        let json = serde_json::json!({
+            "report": report,
+            "orders": result.orders,
+            "equity_curve": result.equity_curve,
+        });
+        std::fs::write(&out, serde_json::to_string_pretty(&json)?)?;
+        println!("report written to {}", out.display());
+    }
+
+    Ok(())
+}
+
+fn print_report(r: &BacktestReport) {
+    println!("bot:            {}", r.bot_name);
+    println!("capital:        {:.2} -> {:.2}", r.initial_capital, r.final_capital);
+    println!("net pnl:        {:.2}", r.net_pnl);
+    println!("trades:         {} ({} wins, {} losses)", r.trade_count, r.wins, r.losses);
+    println!("win rate:       {:.2}%", r.win_rate);
+    println!("profit factor:  {:.2}", r.profit_factor);
+    println!("max drawdown:   {:.2} ({:.2}%)", r.max_drawdown, r.max_drawdown_pct);
+    println!("fees:           {:.2}", r.fees);
+}
+
+fn parse_args(raw: Vec<String>) -> Result<Args, String> {
+    let mut strategy = None;
+    let mut symbol = None;
+    let mut timeframe = None;
+    let mut file = None;
+    let risk = &config::get().risk;
+    let mut args = Args {
+        strategy: String::new(),
+        params: Value::Null,
+        symbol: Symbol::new("SOLUSDT"),
+        timeframe: Timeframe::Min1,
+        file: PathBuf::new(),
+        from: None,
+        to: None,
+        out: None,
+        capital: risk.capital,
+        leverage: risk.leverage,
+        take_profit_ratio: risk.take_profit_ratio,
+        stop_loss_ratio: risk.stop_loss_ratio,
+        trailing_stop_activation_point: risk.trailing_stop_activation_point,
+        slippage: risk.exit_slippage,
+    };
+
+    let mut iter = raw.into_iter();
+    while let Some(flag) = iter.next() {
+        let value = iter.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--strategy" => strategy = Some(value),
+            "--params" => args.params = serde_json::from_str(&value).map_err(|e| format!("invalid --params: {}", e))?,
+            "--symbol" => symbol = Some(Symbol::intern(&value)?),
+            "--timeframe" => timeframe = Some(value.parse::<Timeframe>()?),
+            "--file" => file = Some(PathBuf::from(value)),
+            "--from" => args.from = Some(parse_date(&value)?),
+            "--to" => args.to = Some(parse_date(&value)?),
+            "--out" => args.out = Some(PathBuf::from(value)),
+            "--capital" => args.capital = parse_number(&flag, &value)?,
+            "--leverage" => args.leverage = parse_number(&flag, &value)?,
+            "--take-profit" => args.take_profit_ratio = parse_number(&flag, &value)?,
+            "--stop-loss" => args.stop_loss_ratio = parse_number(&flag, &value)?,
+            "--trailing" => args.trailing_stop_activation_point = parse_number(&flag, &value)?,
+            "--slippage" => args.slippage = parse_number(&flag, &value)?,
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+
+    args.strategy = strategy.ok_or("--strategy is required")?;
+    args.symbol = symbol.ok_or("--symbol is required")?;
+    args.timeframe = timeframe.ok_or("--timeframe is required")?;
+    args.file = file.ok_or("--file is required")?;
+    Ok(args)
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<f64, String> {
+    value.parse::<f64>().map_err(|_| format!("invalid number for {}: {}", flag, value))
+}
+
+/// Dates are UTC, like the kline open times.
+fn parse_date(value: &str) -> Result<u64, String> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| format!("invalid date: {}", value))?;
+    Ok(naive.and_utc().timestamp_millis() as u64)
+}
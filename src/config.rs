@@ -0,0 +1,316 @@
+use crate::constants::{EXIT_SLIPPAGE_RATIO, MAX_LEVERAGE, MIN_CAPITAL_TO_STOP};
+use crate::enums::{Symbol, Timeframe};
+use crate::strategy::strategy::{find_strategy, STRATEGIES};
+use serde_json::Value;
+use crate::symbols::SymbolInfo;
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+pub const DEFAULT_CONFIG_PATH: &str = "traderrs.toml";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    /// Hours east of UTC used for bot timestamps and timeframe boundaries.
+    pub time_zone: i32,
+    pub risk: RiskConfig,
+    pub bots: BotsConfig,
+    /// Trading rules that take precedence over `/fapi/v1/exchangeInfo`.
+    pub symbols: Vec<SymbolInfo>,
+    /// A saved `/fapi/v1/exchangeInfo` response used instead of asking the exchange.
+    pub symbols_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `~/` is expanded to the home directory.
+    pub path: String,
+    /// Seconds between bot state snapshots, opens and closes are saved right away as well.
+    pub snapshot_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    pub capital: f64,
+    pub leverage: f64,
+    pub take_profit_ratio: f64,
+    pub stop_loss_ratio: f64,
+    pub trailing_stop_activation_point: f64,
+    /// A bot at or below this capital stops trading and is reset.
+    pub min_capital_to_stop: f64,
+    pub exit_slippage: f64,
+}
+
+/// Default bot set, every strategy on every timeframe and symbol. Bots of the set are added on
+/// startup unless they were added before, so extending it adds bots and deleted ones stay deleted.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotsConfig {
+    #[serde(deserialize_with = "from_str_list")]
+    pub timeframes: Vec<Timeframe>,
+    pub strategies: Vec<String>,
+    #[serde(deserialize_with = "symbol_list")]
+    pub symbols: Vec<Symbol>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            database: DatabaseConfig::default(),
+            time_zone: 3,
+            risk: RiskConfig::default(),
+            bots: BotsConfig::default(),
+            symbols: Vec::new(),
+            symbols_file: None,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { listen: "0.0.0.0:3030".to_string() }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self { path: "~/db/traders_db.sqlite".to_string(), snapshot_interval_secs: 60 }
+    }
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            capital: 100.0,
+            leverage: 10.0,
+            take_profit_ratio: 0.8,
+            stop_loss_ratio: 0.4,
+            trailing_stop_activation_point: 0.1,
+            min_capital_to_stop: MIN_CAPITAL_TO_STOP,
+            exit_slippage: EXIT_SLIPPAGE_RATIO,
+        }
+    }
+}
+
+impl Default for BotsConfig {
+    fn default() -> Self {
+        Self {
+            timeframes: vec![Timeframe::Min1, Timeframe::Min5, Timeframe::Min15, Timeframe::Min30, Timeframe::Hour1, Timeframe::Hour4],
+            strategies: STRATEGIES.iter().map(|s| s.to_string()).collect(),
+            symbols: ["SOLUSDT", "ETHUSDT", "BNBUSDT", "BTCUSDT"].iter().map(|s| Symbol::new(s)).collect(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path` (defaults when it doesn't exist), applies `TRADERRS_*` env overrides and validates.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut config = if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            Self::parse(&content)?
+        } else {
+            Self::default()
+        };
+
+        config.apply_env(|key| env::var(key).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(toml::from_str(content)?)
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        if let Some(v) = var("TRADERRS_LISTEN") { self.server.listen = v; }
+        if let Some(v) = var("TRADERRS_DB_PATH") { self.database.path = v; }
+        if let Some(v) = var("TRADERRS_SNAPSHOT_INTERVAL_SECS") { self.database.snapshot_interval_secs = parse_env("TRADERRS_SNAPSHOT_INTERVAL_SECS", &v)?; }
+        if let Some(v) = var("TRADERRS_TIME_ZONE") { self.time_zone = parse_env("TRADERRS_TIME_ZONE", &v)?; }
+        if let Some(v) = var("TRADERRS_CAPITAL") { self.risk.capital = parse_env("TRADERRS_CAPITAL", &v)?; }
+        if let Some(v) = var("TRADERRS_LEVERAGE") { self.risk.leverage = parse_env("TRADERRS_LEVERAGE", &v)?; }
+        if let Some(v) = var("TRADERRS_TAKE_PROFIT_RATIO") { self.risk.take_profit_ratio = parse_env("TRADERRS_TAKE_PROFIT_RATIO", &v)?; }
+        if let Some(v) = var("TRADERRS_STOP_LOSS_RATIO") { self.risk.stop_loss_ratio = parse_env("TRADERRS_STOP_LOSS_RATIO", &v)?; }
+        if let Some(v) = var("TRADERRS_TRAILING_STOP_ACTIVATION_POINT") { self.risk.trailing_stop_activation_point = parse_env("TRADERRS_TRAILING_STOP_ACTIVATION_POINT", &v)?; }
+        if let Some(v) = var("TRADERRS_MIN_CAPITAL_TO_STOP") { self.risk.min_capital_to_stop = parse_env("TRADERRS_MIN_CAPITAL_TO_STOP", &v)?; }
+        if let Some(v) = var("TRADERRS_EXIT_SLIPPAGE") { self.risk.exit_slippage = parse_env("TRADERRS_EXIT_SLIPPAGE", &v)?; }
+        if let Some(v) = var("TRADERRS_TIMEFRAMES") { self.bots.timeframes = parse_env_list("TRADERRS_TIMEFRAMES", &v)?; }
+        if let Some(v) = var("TRADERRS_STRATEGIES") { self.bots.strategies = parse_env_list("TRADERRS_STRATEGIES", &v)?; }
+        if let Some(v) = var("TRADERRS_SYMBOLS_FILE") { self.symbols_file = Some(v); }
+        if let Some(v) = var("TRADERRS_SYMBOLS") { self.bots.symbols = v.split(',').map(Symbol::intern).collect::<Result<_, _>>().map_err(|e| format!("TRADERRS_SYMBOLS: {}", e))?; }
+        Ok(())
+    }
+
+    /// Collects every problem instead of stopping at the first one.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        let risk = &self.risk;
+
+        if self.server.listen.parse::<std::net::SocketAddr>().is_err() {
+            errors.push(format!("server.listen: not a socket address: {}", self.server.listen));
+        }
+        if self.database.path.trim().is_empty() {
+            errors.push("database.path: must not be empty".to_string());
+        }
+        if self.database.snapshot_interval_secs == 0 {
+            errors.push("database.snapshot_interval_secs: must be positive".to_string());
+        }
+        if !(-12..=14).contains(&self.time_zone) {
+            errors.push(format!("time_zone: {} is outside -12..=14", self.time_zone));
+        }
+        if risk.min_capital_to_stop.is_nan() || risk.min_capital_to_stop < 0.0 {
+            errors.push("risk.min_capital_to_stop: must not be negative".to_string());
+        }
+        if !risk.capital.is_finite() || risk.capital <= risk.min_capital_to_stop {
+            errors.push(format!("risk.capital: must be above risk.min_capital_to_stop ({})", risk.min_capital_to_stop));
+        }
+        if !(1.0..=MAX_LEVERAGE).contains(&risk.leverage) {
+            errors.push(format!("risk.leverage: must be between 1 and {}", MAX_LEVERAGE));
+        }
+        if !(risk.take_profit_ratio > 0.0 && risk.take_profit_ratio < 100.0) {
+            errors.push("risk.take_profit_ratio: must be between 0 and 100".to_string());
+        }
+        // beyond 100 / leverage percent the position is liquidated before the stop triggers
+        if !(risk.stop_loss_ratio > 0.0 && risk.stop_loss_ratio < 100.0 / risk.leverage) {
+            errors.push(format!("risk.stop_loss_ratio: must be between 0 and {:.2}", 100.0 / risk.leverage));
+        }
+        if !(0.0..100.0).contains(&risk.trailing_stop_activation_point) {
+            errors.push("risk.trailing_stop_activation_point: must be between 0 and 100".to_string());
+        }
+        if !(0.0..100.0).contains(&risk.exit_slippage) {
+            errors.push("risk.exit_slippage: must be between 0 and 100".to_string());
+        }
+        for info in self.symbols.iter() {
+            if !(info.tick_size > 0.0 && info.step_size > 0.0 && info.min_notional >= 0.0) {
+                errors.push(format!("symbols.{}: tick_size and step_size must be positive", info.symbol));
+            }
+        }
+        for name in self.bots.strategies.iter() {
+            if find_strategy(name, &Value::Null).is_err() {
+                errors.push(format!("bots.strategies: unknown strategy {}", name));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("invalid configuration:\n  {}", errors.join("\n  ")))
+        }
+    }
+
+    pub fn database_path(&self) -> PathBuf {
+        match self.database.path.strip_prefix("~/") {
+            Some(rest) => env::home_dir().unwrap_or_default().join(rest),
+            None => PathBuf::from(&self.database.path),
+        }
+    }
+}
+
+/// Makes `config` the one `get` returns, only the first call wins.
+pub fn init(config: Config) -> &'static Config {
+    CONFIG.get_or_init(|| config)
+}
+
+/// The loaded configuration, defaults when `init` was never called (tests).
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+fn from_str_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| s.parse::<T>().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn symbol_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Symbol>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| Symbol::intern(s).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn parse_env<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.trim().parse::<T>().map_err(|_| format!("{}: invalid value {}", key, value))
+}
+
+fn parse_env_list<T: FromStr>(key: &str, value: &str) -> Result<Vec<T>, String> {
+    value.split(',').map(|v| parse_env(key, v)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn file_values_and_env_overrides() {
+        let mut config = Config::parse(r#"
+            time_zone = 0
+
+            [server]
+            listen = "127.0.0.1:8080"
+
+            [risk]
+            leverage = 5
+            stop_loss_ratio = 1.0
+
+            [bots]
+            timeframes = ["15m", "1h"]
+            strategies = ["EmaMacd"]
+            symbols = ["SOLUSDT"]
+        "#).unwrap();
+
+        let env = HashMap::from([("TRADERRS_CAPITAL", "250"), ("TRADERRS_SYMBOLS", "ETHUSDT,BTCUSDT")]);
+        config.apply_env(|key| env.get(key).map(|v| v.to_string())).unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.server.listen, "127.0.0.1:8080");
+        assert_eq!(config.time_zone, 0);
+        assert_eq!((config.risk.capital, config.risk.leverage, config.risk.take_profit_ratio), (250.0, 5.0, 0.8));
+        assert_eq!(config.bots.timeframes, vec![Timeframe::Min15, Timeframe::Hour1]);
+        assert_eq!(config.bots.symbols, vec![Symbol::new("ETHUSDT"), Symbol::new("BTCUSDT")]);
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let mut config = Config::default();
+        config.server.listen = "nowhere".to_string();
+        config.risk.leverage = 20.0;
+        config.risk.stop_loss_ratio = 6.0;
+        config.bots.strategies.push("Nope".to_string());
+
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("server.listen"));
+        assert!(err.contains("risk.stop_loss_ratio"));
+        assert!(err.contains("unknown strategy Nope"));
+    }
+
+    #[test]
+    fn unknown_keys_and_values_are_parse_errors() {
+        assert!(Config::parse("[risk]\nleverag = 5").is_err());
+        assert!(Config::parse("[bots]\ntimeframes = [\"7m\"]").is_err());
+    }
+}
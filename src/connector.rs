@@ -1,16 +1,42 @@
 use crate::enums::{Symbol, Timeframe};
-use crate::models::models::Candle;
+use crate::models::models::{Candle, PriceRange};
+use crate::symbols;
+use crate::symbols::SymbolInfo;
+use async_trait::async_trait;
+use chrono::Utc;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::Value;
 use std::error::Error;
-use std::sync::atomic::{AtomicI64, Ordering};
 
 #[derive(Deserialize)]
 struct PriceResponse {
     price: String,
 }
 
+/// Source of market data the managers and bots trade against.
+///
+/// `BinanceConnector` is the live implementation, anything else (stubs, replays)
+/// only has to provide prices and klines to drive the whole engine.
+#[async_trait]
+pub trait MarketDataSource: Send + Sync {
+    async fn get_price(&self, symbol: &Symbol) -> Result<f64, Box<dyn Error + Send + Sync>>;
+
+    async fn get_candles(
+        &self,
+        symbol: Symbol,
+        timeframe: Timeframe,
+        limit: i32,
+    ) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>>;
+
+    /// High, low and last price seen since the previous call for `symbol`.
+    ///
+    /// Sources that only know the current price return it as a flat range.
+    async fn take_price_range(&self, symbol: &Symbol) -> Result<PriceRange, Box<dyn Error + Send + Sync>> {
+        Ok(PriceRange::from_price(self.get_price(symbol).await?))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BinanceConnector {
     client: Client,
@@ -25,29 +51,32 @@ impl BinanceConnector {
         }
     }
 
-    pub async fn get_price(&self, symbol: &Symbol) -> Result<f64, Box<dyn Error + Send + Sync>> {
-        // let path = "test.csv";
-        // let file = File::open(path)?;
-        // let reader = io::BufReader::new(file);
-        // let mut index = 0;
-        // let i = get_index();
-        // for s in reader.lines() {
-        //     index += 1;
-        //     if index == 1 { continue; }
-        //     let line = s?;
-        //     let fields: Vec<&str> = line.split(',').collect();
-        //     if i == index {
-        //         let pr = fields[4].parse().unwrap_or(0.0);
-        //         return Ok(pr);
-        //     }
-        //
-        // }
-        //
-        // Ok(0.0)
+    /// Trading rules of every symbol listed on USDⓈ-M futures.
+    pub async fn get_exchange_info(&self) -> Result<Vec<SymbolInfo>, Box<dyn Error + Send + Sync>> {
+        let body = self
+            .client
+            .get("https://fapi.binance.com/fapi/v1/exchangeInfo")
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        Ok(symbols::parse_exchange_info(&body))
+    }
+}
+
+impl Default for BinanceConnector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
+#[async_trait]
+impl MarketDataSource for BinanceConnector {
+    async fn get_price(&self, symbol: &Symbol) -> Result<f64, Box<dyn Error + Send + Sync>> {
         let url = format!(
             "https://fapi.binance.com/fapi/v2/ticker/price?symbol={}",
-            symbol.to_string()
+            symbol
         );
 
         let res = self.client.get(&url).send().await?;
@@ -57,43 +86,16 @@ impl BinanceConnector {
         Ok(price)
     }
 
-    pub async fn get_candles(
+    async fn get_candles(
         &self,
         symbol: Symbol,
         timeframe: Timeframe,
         limit: i32,
     ) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
-        // let path = "test.csv";
-        // let file = File::open(path)?;
-        // let reader = io::BufReader::new(file);
-        // let mut index = 0;
-        // let i = get_index();
-        // let mut v = Vec::new();
-        // for s in reader.lines() {
-        //     index += 1;
-        //     if index == 1 { continue; }
-        //     let line = s?;
-        //     let fields: Vec<&str> = line.split(',').collect();
-        //     v.push(Candle {
-        //         close: fields[4].parse().unwrap_or(0.0),
-        //         open: 0.0,
-        //         high: 0.0,
-        //         low: 0.0,
-        //         volume: 0.0,
-        //         open_time: 0,
-        //     });
-        //     if i == index {
-        //         return Ok(v);
-        //     }
-        // }
-        //
-        // Ok(v)
-
-
         let url = format!(
             "https://fapi.binance.com/fapi/v1/klines?symbol={}&interval={}&limit={}",
-            symbol.to_string(),
-            timeframe.to_string(),
+            symbol,
+            timeframe,
             limit
         );
 
@@ -105,34 +107,46 @@ impl BinanceConnector {
             .json::<Vec<Value>>()
             .await?;
 
-        let mut candles: Vec<Candle> = Vec::new();
-        
-        for entry in res {
-            let open_time = entry[0].as_u64().unwrap_or_default();
-            let open = entry[1].as_str().unwrap_or("0").parse::<f64>()?;
-            let high = entry[2].as_str().unwrap_or("0").parse::<f64>()?;
-            let low = entry[3].as_str().unwrap_or("0").parse::<f64>()?;
-            let close = entry[4].as_str().unwrap_or("0").parse::<f64>()?;
-            let volume = entry[5].as_str().unwrap_or("0").parse::<f64>()?;
-
-            candles.push(Candle {
-                close,
-                open,
-                high,
-                low,
-                open_time,
-                volume,
-            })
-        }
-
-        Ok(candles)
+        parse_klines(&res, timeframe)
     }
 }
 
-#[allow(dead_code)]
-static BOT_ID_COUNTER: AtomicI64 = AtomicI64::new(201);
+/// Parses klines in the `/fapi/v1/klines` array format, numbers may be strings or plain numbers.
+///
+/// Dumps without the close time column get it from `timeframe`.
+pub fn parse_klines(entries: &[Value], timeframe: Timeframe) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+    let mut candles: Vec<Candle> = Vec::with_capacity(entries.len());
+    // the REST response has no closed flag, the last kline is forming until its close time passes
+    let now = Utc::now().timestamp_millis().max(0) as u64;
 
-#[allow(dead_code)]
-fn get_index() -> i64 {
-    BOT_ID_COUNTER.fetch_add(1, Ordering::Relaxed)
-}
\ No newline at end of file
+    for entry in entries {
+        let open_time = entry[0].as_u64().unwrap_or_default();
+        let open = value_to_f64(&entry[1])?;
+        let high = value_to_f64(&entry[2])?;
+        let low = value_to_f64(&entry[3])?;
+        let close = value_to_f64(&entry[4])?;
+        let volume = value_to_f64(&entry[5])?;
+        let close_time = entry[6].as_u64().unwrap_or(open_time + timeframe.duration_ms() - 1);
+        let is_closed = close_time < now;
+
+        candles.push(Candle {
+            close,
+            open,
+            high,
+            low,
+            open_time,
+            close_time,
+            volume,
+            is_closed,
+        })
+    }
+
+    Ok(candles)
+}
+
+pub fn value_to_f64(value: &Value) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    match value {
+        Value::Number(n) => Ok(n.as_f64().unwrap_or_default()),
+        _ => Ok(value.as_str().unwrap_or("0").parse::<f64>()?),
+    }
+}
@@ -1,3 +1,11 @@
+/// Default for `risk.min_capital_to_stop`.
+pub const MIN_CAPITAL_TO_STOP: f64 = 85.0;
 
+/// Default adverse slippage applied to stop-loss and take-profit fills, in percent of the level price.
+pub const EXIT_SLIPPAGE_RATIO: f64 = 0.02;
 
-pub const MIN_CAPITAL_TO_STOP: f64 = 85.0;
+/// Closed candles a strategy scan sees, enough for the 200 EMA. Live scans and backtests use the same window.
+pub const CANDLES_WINDOW: usize = 202;
+
+/// Highest leverage Binance allows on USDⓈ-M perpetuals.
+pub const MAX_LEVERAGE: f64 = 125.0;
@@ -1,29 +1,32 @@
-use std::cmp::min;
-use crate::connector::BinanceConnector;
-use crate::enums::{OrderCommand, Symbol, Timeframe};
+use crate::config;
+use crate::constants::CANDLES_WINDOW;
+use crate::connector::MarketDataSource;
+use crate::enums::{BotMode, OrderCommand, Symbol, Timeframe};
 use crate::models::bot::Bot;
-use crate::models::models::{Candle, Container, SharedVec, StrategyContainer};
+use crate::models::bot_registry::{BotHandle, BotRegistry};
+use crate::models::models::{Candle, Container, StrategyContainer};
 use crate::tools;
 use crate::tools::wait_until_next_aligned_tick;
 use chrono::{DateTime, FixedOffset, Timelike};
-use log::{debug, error};
+use log::{debug, error, warn};
 use std::collections::HashMap;
+use std::error::Error;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
-#[derive()]
 pub struct EntryManager {
-    bots: Arc<SharedVec<Bot>>,
+    bots: Arc<BotRegistry>,
     bots_data: HashMap<Timeframe, HashMap<Symbol, ()>>,
-    connector: Arc<BinanceConnector>,
+    connector: Arc<dyn MarketDataSource>,
     c: Arc<Container>,
     strategy_container: StrategyContainer,
 }
 
 impl EntryManager {
-    pub fn new(bots: Arc<SharedVec<Bot>>, connector: Arc<BinanceConnector>, c: Arc<Container>) -> Self {
+    pub fn new(bots: Arc<BotRegistry>, connector: Arc<dyn MarketDataSource>, c: Arc<Container>) -> Self {
         Self {
             bots,
             bots_data: HashMap::new(),
@@ -33,44 +36,50 @@ impl EntryManager {
         }
     }
 
-    pub async fn start(&mut self) {
+    /// Scans once a minute until `token` is cancelled, a scan in progress is always finished.
+    pub async fn start(&mut self, token: CancellationToken) {
         debug!("Starting Entry Manager...");
         let mut now: DateTime<FixedOffset>;
         let sleep_time = 60;
         let extra_sleep_time = 3;
-        let bots: &mut Vec<Bot>;
-        unsafe {
-            bots = &mut *self.bots.0.get();
-        }
-
 
-        self.update_bots_data(bots).await;
+        loop {
+            let wait = async {
+                wait_until_next_aligned_tick(Duration::from_secs(sleep_time)).await;
+                tokio::time::sleep(Duration::from_secs(extra_sleep_time)).await;
+            };
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = wait => {}
+            }
 
-        wait_until_next_aligned_tick(Duration::from_secs(sleep_time)).await;
-        tokio::time::sleep(Duration::from_secs(extra_sleep_time)).await;
+            now = tools::get_date(config::get().time_zone);
 
-        loop {
-            now = tools::get_date(3);
+            self.tick(&now).await;
+        }
+        debug!("Entry Manager stopped");
+    }
 
-            // if now.hour() == 0 && now.minute() == 0 {
-            //     self.save_and_reset_bots(bots).await;
-            // }
+    /// Runs a single scan at `now`, the live loop calls it once a minute and replays drive it directly.
+    pub async fn tick(&mut self, now: &DateTime<FixedOffset>) {
+        let bots = self.bots.handles();
 
-            self.update_candles(&now).await;
+        self.update_bots_data(&bots).await;
 
-            self.calculate_ta().await;
+        self.update_candles(now).await;
 
-            self.scan_bots(bots, &now).await;
+        self.calculate_ta().await;
 
-            self.strategy_container.reset();
+        self.scan_bots(&bots, now).await;
 
-            wait_until_next_aligned_tick(Duration::from_secs(sleep_time)).await;
-            tokio::time::sleep(Duration::from_secs(extra_sleep_time)).await;
-        }
+        self.strategy_container.reset();
     }
 
-    async fn scan_bots(&mut self, bots: &mut Vec<Bot>, now: &DateTime<FixedOffset>) {
-        for bot in bots.iter_mut() {
+    async fn scan_bots(&mut self, bots: &[Arc<BotHandle>], now: &DateTime<FixedOffset>) {
+        let mut opened = Vec::new();
+
+        for handle in bots.iter() {
+            let mut bot = handle.lock().await;
             if bot.is_not_allowed_for_scanning(now) { continue; }
 
             bot.last_scanned = *now;
@@ -81,8 +90,16 @@ impl EntryManager {
 
             match command {
                 OrderCommand::Long | OrderCommand::Short => {
-                    if let Err(e) = bot.open_position(&command, &self.connector).await {
-                        error!("Failed to open position for {}: {}", bot.name, e);
+                    let result = match bot.mode {
+                        BotMode::Paper => bot.open_position(&command, self.connector.as_ref(), *now).await,
+                        BotMode::Live => self.open_live_position(&mut bot, &command, *now).await,
+                    };
+                    match result {
+                        Ok(()) => opened.push(bot.clone()),
+                        Err(e) => {
+                            error!("Failed to open position for {}: {}", bot.name, e);
+                            bot.log = e.to_string();
+                        }
                     }
                 }
                 _ => {
@@ -90,12 +107,31 @@ impl EntryManager {
                 }
             }
         }
+
+        // a restart resumes these positions from the saved state
+        if !opened.is_empty() {
+            if let Err(e) = self.c.repository.save_bot_state(opened).await {
+                error!("Failed to save opened positions: {}", e);
+            }
+        }
+    }
+
+    async fn open_live_position(&self, bot: &mut Bot, command: &OrderCommand, now: DateTime<FixedOffset>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let executor = self.c.executor.as_ref().ok_or("live bot without exchange credentials")?;
+        bot.can_open_position()?;
+        let price = self.connector.get_price(&bot.symbol).await?;
+        executor.open_position(bot, command, price, now).await
     }
 
-    async fn update_bots_data(&mut self, bots: &mut Vec<Bot>) {
+    async fn update_bots_data(&mut self, bots: &[Arc<BotHandle>]) {
         self.bots_data.clear();
+        self.strategy_container.clear_requirements();
 
-        for bot in bots.iter() {
+        for handle in bots.iter() {
+            let bot = handle.snapshot();
+            if let Some(strategy) = &bot.strategy {
+                self.strategy_container.require(bot.timeframe, bot.symbol, &strategy.indicators());
+            }
             self.bots_data
                 .entry(bot.timeframe)
                 .or_insert(HashMap::new())
@@ -103,34 +139,14 @@ impl EntryManager {
         }
     }
 
-    async fn save_and_reset_bots(&mut self, bots: &mut Vec<Bot>) {
-        self.c.repository.create_bots_in_batch(bots).expect("error creating bots");
-
-        for b in bots.iter_mut() {
-            b.reset();
-        }
-    }
-
     async fn update_candles(&mut self, now: &DateTime<FixedOffset>) {
         let connector = Arc::clone(&self.connector);
         let semaphore = Arc::new(Semaphore::new(25)); // Limit concurrent tasks
         let mut fetch_tasks = Vec::new();
 
-        let timeframes_to_fetch = [
-            Timeframe::Min1,
-            Timeframe::Min5,
-            Timeframe::Min15,
-            Timeframe::Min30,
-            Timeframe::Hour1,
-            Timeframe::Hour4,
-        ].into_iter().filter(|tf| {
-            *tf == Timeframe::Min1
-              || (*tf == Timeframe::Min5 && now.minute() % 5 == 0)
-              || (*tf == Timeframe::Min15 && now.minute() % 15 == 0)
-              || (*tf == Timeframe::Min30 && now.minute() % 30 == 0)
-              || (*tf == Timeframe::Hour1 && now.minute() % 60 == 0)
-              || (*tf == Timeframe::Hour4 && now.hour() % 4 == 0 && now.minute() & 60 == 0)
-        });
+        let now_ms = now.timestamp_millis().max(0) as u64;
+        let minute = tools::minute_start_ms(now);
+        let timeframes_to_fetch = Timeframe::ALL.into_iter().filter(|tf| tf.is_boundary(minute));
 
         for tf in timeframes_to_fetch {
             if let Some(inner_map) = self.bots_data.get(&tf) {
@@ -143,10 +159,22 @@ impl EntryManager {
 
                     let handle: JoinHandle<Option<((Timeframe, Symbol), Vec<Candle>)>> = tokio::spawn(async move {
                         let _permit = permit; // Drop when task is done
-                        match connector.get_candles(smb_copy, tf_copy, 202).await {
-                            Ok(candles) => Some((key, candles)),
+                        // one extra for the forming candle the exchange returns last
+                        let candles = match connector.get_candles(smb_copy, tf_copy, CANDLES_WINDOW as i32 + 1).await {
+                            Ok(candles) => candles,
                             Err(e) => {
                                 error!("Error fetching candles: {}", e);
+                                return None;
+                            }
+                        };
+                        match tools::closed_candles(candles, tf_copy, now_ms) {
+                            Ok(mut candles) => {
+                                let closed = candles.iter().filter(|c| c.is_closed).count();
+                                candles.drain(..closed.saturating_sub(CANDLES_WINDOW));
+                                Some((key, candles))
+                            }
+                            Err(e) => {
+                                warn!("Skipping {:?}: {}", smb_copy, e);
                                 None
                             }
                         }
@@ -161,7 +189,7 @@ impl EntryManager {
 
         for task in fetch_tasks {
             if let Ok(Some((key, candles))) = task.await {
-                self.strategy_container.candles_map.insert(key, candles);
+                self.strategy_container.insert_candles(key.0, key.1, candles);
             }
         }
     }
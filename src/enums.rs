@@ -1,48 +1,122 @@
 use rusqlite::types::{FromSql, FromSqlResult, ToSqlOutput, ValueRef};
 use rusqlite::ToSql;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, Hash, PartialEq)]
-pub enum Symbol {
-    SolUsdt,
-    BtcUsdt,
-    EthUsdt,
-    BnbUsdt,
+/// A futures contract name like `SOLUSDT`, any listed symbol works.
+///
+/// Names are interned so the type stays `Copy` and can key the candle and price maps. Interned
+/// names are never freed, so only config, exchangeInfo and our own database intern them, parsing
+/// (`FromStr`, `Deserialize`) only finds names that are already known.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct Symbol(&'static str);
+
+static INTERNED_SYMBOLS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+// quote assets stripped for bot names, longest first
+const QUOTE_ASSETS: [&str; 3] = ["USDT", "USDC", "BUSD"];
+
+fn interned() -> &'static Mutex<HashSet<&'static str>> {
+    INTERNED_SYMBOLS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn normalize(name: &str) -> Result<String, String> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() || !trimmed.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid Symbol: {}", name));
+    }
+    Ok(trimmed.to_uppercase())
+}
+
+impl Symbol {
+    /// Upper-cases `name`, so the legacy `SolUsdt` spelling maps to `SOLUSDT`.
+    ///
+    /// Interns the name for good, never call it with untrusted input.
+    pub fn new(name: &str) -> Self {
+        let name = name.trim().to_uppercase();
+        let mut interned = interned().lock().unwrap();
+        if let Some(s) = interned.get(name.as_str()) {
+            return Symbol(s);
+        }
+        let s: &'static str = Box::leak(name.into_boxed_str());
+        interned.insert(s);
+        Symbol(s)
+    }
+
+    /// Checks and interns a trusted name.
+    pub fn intern(name: &str) -> Result<Self, String> {
+        normalize(name).map(|name| Symbol::new(&name))
+    }
+
+    /// Serde `deserialize_with` for trusted sources such as the config file.
+    pub fn deserialize_trusted<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Symbol::intern(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    /// `SOLUSDT` -> `SOL`
+    pub fn base_asset(&self) -> &'static str {
+        QUOTE_ASSETS
+            .iter()
+            .find_map(|q| self.0.strip_suffix(q).filter(|b| !b.is_empty()))
+            .unwrap_or(self.0)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
 }
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl ToSql for Symbol {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        let s = match self {
-            Symbol::SolUsdt => "SolUsdt",
-            Symbol::BtcUsdt => "BtcUsdt",
-            Symbol::EthUsdt => "EthUsdt",
-            Symbol::BnbUsdt => "BnbUsdt",
-        };
-
-        Ok(rusqlite::types::ToSqlOutput::from(s))
+        Ok(ToSqlOutput::from(self.0))
     }
 }
+
+// rows were written by us, so their names are trusted
 impl FromSql for Symbol {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        match value.as_str()? {
-            "SolUsdt" => Ok(Symbol::SolUsdt),
-            "BtcUsdt" => Ok(Symbol::BtcUsdt),
-            "EthUsdt" => Ok(Symbol::EthUsdt),
-            "BnbUsdt" => Ok(Symbol::BnbUsdt),
-            other => Err(rusqlite::types::FromSqlError::Other(Box::new(
-                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid Symbol: {}", other))
-            ))),
-
-        }
+        Symbol::intern(value.as_str()?).map_err(|e| {
+            rusqlite::types::FromSqlError::Other(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+        })
     }
 }
 
-impl Symbol {
-    pub fn to_string(&self) -> String {
-        match self {
-            Symbol::SolUsdt => String::from("SOLUSDT"),
-            Symbol::BtcUsdt => String::from("BTCUSDT"),
-            Symbol::EthUsdt => String::from("ETHUSDT"),
-            Symbol::BnbUsdt => String::from("BNBUSDT"),
+impl FromStr for Symbol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = normalize(s)?;
+        match interned().lock().unwrap().get(name.as_str()) {
+            Some(s) => Ok(Symbol(s)),
+            None => Err(format!("unknown symbol: {}", name)),
         }
     }
 }
@@ -50,40 +124,47 @@ impl Symbol {
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, Hash, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Timeframe {
     #[serde(rename = "1m")] Min1,
+    #[serde(rename = "3m")] Min3,
     #[serde(rename = "5m")] Min5,
     #[serde(rename = "15m")] Min15,
     #[serde(rename = "30m")] Min30,
     #[serde(rename = "1h")] Hour1,
+    #[serde(rename = "2h")] Hour2,
     #[serde(rename = "4h")] Hour4,
+    #[serde(rename = "6h")] Hour6,
+    #[serde(rename = "8h")] Hour8,
+    #[serde(rename = "12h")] Hour12,
+    #[serde(rename = "1d")] Day1,
+    #[serde(rename = "3d")] Day3,
+    #[serde(rename = "1w")] Week1,
 }
 impl ToSql for Timeframe {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-        let s = match self {
-            Timeframe::Min1 => "1m",
-            Timeframe::Min5 => "5m",
-            Timeframe::Min15 => "15m",
-            Timeframe::Min30 => "30m",
-            Timeframe::Hour1 => "1h",
-            Timeframe::Hour4 => "4h",
-        };
-
-        Ok(rusqlite::types::ToSqlOutput::from(s))
+        Ok(rusqlite::types::ToSqlOutput::from(self.as_str()))
     }
 }
 impl FromSql for Timeframe {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        match value.as_str()? {
-            "1m" => Ok(Timeframe::Min1),
-            "5m" => Ok(Timeframe::Min5),
-            "15m" => Ok(Timeframe::Min15),
-            "30m" => Ok(Timeframe::Min30),
-            "1h" => Ok(Timeframe::Hour1),
-            "4h" => Ok(Timeframe::Hour4),
-            other => Err(rusqlite::types::FromSqlError::Other(Box::new(
-                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid Timeframe: {}", other))
-            ))),
+        value.as_str()?.parse().map_err(|e: String| {
+            rusqlite::types::FromSqlError::Other(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+        })
+    }
+}
 
-        }
+impl FromStr for Timeframe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Timeframe::ALL
+            .into_iter()
+            .find(|tf| tf.as_str() == s)
+            .ok_or_else(|| format!("Invalid Timeframe: {}", s))
+    }
+}
+
+impl Display for Timeframe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -127,15 +208,143 @@ impl FromSql for OrderCommand {
     }
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Hash, Eq, PartialEq, Default)]
+pub enum BotMode {
+    #[default]
+    Paper,
+    Live,
+}
+impl ToSql for BotMode {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let s = match self {
+            BotMode::Paper => "Paper",
+            BotMode::Live => "Live",
+        };
+        Ok(rusqlite::types::ToSqlOutput::from(s))
+    }
+}
+impl FromSql for BotMode {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "Paper" => Ok(BotMode::Paper),
+            "Live" => Ok(BotMode::Live),
+            other => Err(rusqlite::types::FromSqlError::Other(Box::new(
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid BotMode: {}", other))
+            ))),
+        }
+    }
+}
+
+const MINUTE_MS: u64 = 60_000;
+const HOUR_MS: u64 = 60 * MINUTE_MS;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
 impl Timeframe {
-    pub fn to_string(&self) -> &'static str {
+    /// Every kline interval Binance futures serves.
+    pub const ALL: [Timeframe; 14] = [
+        Timeframe::Min1,
+        Timeframe::Min3,
+        Timeframe::Min5,
+        Timeframe::Min15,
+        Timeframe::Min30,
+        Timeframe::Hour1,
+        Timeframe::Hour2,
+        Timeframe::Hour4,
+        Timeframe::Hour6,
+        Timeframe::Hour8,
+        Timeframe::Hour12,
+        Timeframe::Day1,
+        Timeframe::Day3,
+        Timeframe::Week1,
+    ];
+
+    /// The Binance interval name, `1m`, `4h`, `1w`...
+    pub fn as_str(&self) -> &'static str {
         match self {
             Timeframe::Min1 => "1m",
+            Timeframe::Min3 => "3m",
             Timeframe::Min5 => "5m",
             Timeframe::Min15 => "15m",
             Timeframe::Min30 => "30m",
             Timeframe::Hour1 => "1h",
+            Timeframe::Hour2 => "2h",
             Timeframe::Hour4 => "4h",
+            Timeframe::Hour6 => "6h",
+            Timeframe::Hour8 => "8h",
+            Timeframe::Hour12 => "12h",
+            Timeframe::Day1 => "1d",
+            Timeframe::Day3 => "3d",
+            Timeframe::Week1 => "1w",
+        }
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        match self {
+            Timeframe::Min1 => MINUTE_MS,
+            Timeframe::Min3 => 3 * MINUTE_MS,
+            Timeframe::Min5 => 5 * MINUTE_MS,
+            Timeframe::Min15 => 15 * MINUTE_MS,
+            Timeframe::Min30 => 30 * MINUTE_MS,
+            Timeframe::Hour1 => HOUR_MS,
+            Timeframe::Hour2 => 2 * HOUR_MS,
+            Timeframe::Hour4 => 4 * HOUR_MS,
+            Timeframe::Hour6 => 6 * HOUR_MS,
+            Timeframe::Hour8 => 8 * HOUR_MS,
+            Timeframe::Hour12 => 12 * HOUR_MS,
+            Timeframe::Day1 => DAY_MS,
+            Timeframe::Day3 => 3 * DAY_MS,
+            Timeframe::Week1 => 7 * DAY_MS,
+        }
+    }
+
+    // candles are aligned to the unix epoch in UTC, except weeks which open on Monday,
+    // the epoch was a Thursday
+    fn alignment_ms(&self) -> u64 {
+        match self {
+            Timeframe::Week1 => 4 * DAY_MS,
+            _ => 0,
+        }
+    }
+
+    /// Open time of the candle containing `utc_ms`.
+    pub fn open_time(&self, utc_ms: u64) -> u64 {
+        let duration = self.duration_ms();
+        let offset = self.alignment_ms();
+        utc_ms - (utc_ms + duration - offset) % duration
+    }
+
+    /// `true` when a candle of this timeframe opens exactly at `utc_ms`, i.e. the previous one just closed.
+    pub fn is_boundary(&self, utc_ms: u64) -> bool {
+        self.open_time(utc_ms) == utc_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-01-06 00:00 UTC, a Monday
+    const MONDAY_MS: u64 = 1_736_121_600_000;
+
+    #[test]
+    fn boundaries_are_utc_aligned() {
+        assert!(Timeframe::Day1.is_boundary(MONDAY_MS));
+        assert!(!Timeframe::Day1.is_boundary(MONDAY_MS - 3 * HOUR_MS));
+        assert!(Timeframe::Week1.is_boundary(MONDAY_MS));
+        assert!(!Timeframe::Week1.is_boundary(MONDAY_MS + DAY_MS));
+        assert!(Timeframe::Hour4.is_boundary(MONDAY_MS + 4 * HOUR_MS));
+        assert!(!Timeframe::Hour4.is_boundary(MONDAY_MS + 3 * HOUR_MS));
+        assert!(Timeframe::Min3.is_boundary(MONDAY_MS + 9 * MINUTE_MS));
+        assert_eq!(Timeframe::Week1.open_time(MONDAY_MS + 3 * DAY_MS + 5), MONDAY_MS);
+        assert_eq!(Timeframe::Hour12.open_time(MONDAY_MS + 13 * HOUR_MS), MONDAY_MS + 12 * HOUR_MS);
+    }
+
+    #[test]
+    fn interval_names_round_trip() {
+        for tf in Timeframe::ALL {
+            assert_eq!(tf.as_str().parse::<Timeframe>(), Ok(tf));
+            assert_eq!(serde_json::to_string(&tf).unwrap(), format!("\"{}\"", tf));
         }
+        assert!("2d".parse::<Timeframe>().is_err());
     }
 }
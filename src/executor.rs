@@ -0,0 +1,712 @@
+use crate::calculator::{calculate_buy_quantity, calculate_taker_fee};
+use crate::enums::{OrderCommand, Symbol};
+use crate::models::bot::Bot;
+use crate::models::models::Order;
+use crate::symbols;
+use chrono::{DateTime, FixedOffset};
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+pub const BINANCE_FUTURES_URL: &str = "https://fapi.binance.com";
+
+const RECV_WINDOW: u64 = 5000;
+// listen keys expire after 60 minutes without a keepalive
+const LISTEN_KEY_KEEPALIVE: Duration = Duration::from_secs(30 * 60);
+// resting exits are also checked over REST this often, in case the stream dropped an event
+const REST_RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitKind {
+    StopLoss,
+    TakeProfit,
+}
+impl ExitKind {
+    fn order_type(&self) -> &'static str {
+        match self {
+            ExitKind::StopLoss => "STOP_MARKET",
+            ExitKind::TakeProfit => "TAKE_PROFIT_MARKET",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeOrder {
+    pub order_id: i64,
+    pub status: String,
+    #[serde(default)]
+    pub avg_price: String,
+    #[serde(default)]
+    pub executed_qty: String,
+}
+impl ExchangeOrder {
+    pub fn is_filled(&self) -> bool {
+        self.status == "FILLED"
+    }
+
+    pub fn fill(&self) -> OrderFill {
+        OrderFill {
+            avg_price: self.avg_price.parse().unwrap_or_default(),
+            quantity: self.executed_qty.parse().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderFill {
+    pub avg_price: f64,
+    pub quantity: f64,
+}
+
+#[derive(Deserialize)]
+struct UserDataEvent {
+    #[serde(rename = "e")]
+    event: String,
+    #[serde(rename = "o")]
+    order: Option<OrderUpdate>,
+}
+
+#[derive(Deserialize)]
+struct OrderUpdate {
+    #[serde(rename = "i")]
+    order_id: i64,
+    #[serde(rename = "X")]
+    status: String,
+    #[serde(rename = "ap")]
+    avg_price: String,
+    #[serde(rename = "z")]
+    filled_quantity: String,
+}
+
+/// Places and tracks real orders on Binance USDⓈ-M futures for bots in `BotMode::Live`.
+///
+/// Entries are MARKET orders, exits are reduce-only STOP_MARKET and TAKE_PROFIT_MARKET orders
+/// resting on the exchange. Fills come from the user data stream. Each exit order is also checked
+/// over REST while the stream is down, once after every reconnect and every
+/// `REST_RECONCILE_INTERVAL`, so a fill sent while nobody listened is still seen.
+pub struct BinanceExecutor {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    secret: String,
+    fills: Mutex<HashMap<i64, OrderFill>>,
+    stream_connected: AtomicBool,
+    // bumped on every user stream connect, fills before it may have been missed
+    stream_epoch: AtomicU64,
+    // exit orders with the stream epoch and time they were last checked over REST
+    tracked: Mutex<HashMap<i64, (u64, Instant)>>,
+}
+
+impl Debug for BinanceExecutor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinanceExecutor")
+         .field("base_url", &self.base_url)
+         .finish()
+    }
+}
+
+impl BinanceExecutor {
+    pub fn new(base_url: &str, api_key: &str, secret: &str) -> Self {
+        Self {
+            client: Client::builder()
+              .timeout(Duration::from_secs(10))
+              .build()
+              .unwrap(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            secret: secret.to_string(),
+            fills: Mutex::new(HashMap::new()),
+            stream_connected: AtomicBool::new(false),
+            stream_epoch: AtomicU64::new(0),
+            tracked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads `BINANCE_API_KEY` and `BINANCE_API_SECRET`, `None` when live trading isn't configured.
+    pub fn from_env() -> Option<Self> {
+        let api_key = std::env::var("BINANCE_API_KEY").ok()?;
+        let secret = std::env::var("BINANCE_API_SECRET").ok()?;
+        let base_url = std::env::var("BINANCE_FUTURES_URL").unwrap_or(BINANCE_FUTURES_URL.to_string());
+        Some(Self::new(&base_url, &api_key, &secret))
+    }
+
+    pub fn sign(&self, query: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("hmac accepts any key size");
+        mac.update(query.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    async fn signed_request(&self, method: Method, path: &str, params: &[(&str, String)]) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis();
+        let mut query: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        query.push(format!("recvWindow={}", RECV_WINDOW));
+        query.push(format!("timestamp={}", timestamp));
+        let query = query.join("&");
+
+        let url = format!("{}{}?{}&signature={}", self.base_url, path, query, self.sign(&query));
+        let res = self
+            .client
+            .request(method, &url)
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send()
+            .await?;
+
+        let status = res.status();
+        let body: Value = res.json().await?;
+        if !status.is_success() {
+            return Err(format!("binance {} failed with {}: {}", path, status, body).into());
+        }
+        Ok(body)
+    }
+
+    pub async fn place_market_order(&self, symbol: &Symbol, side: &str, quantity: f64, reduce_only: bool) -> Result<ExchangeOrder, Box<dyn Error + Send + Sync>> {
+        let body = self.signed_request(Method::POST, "/fapi/v1/order", &[
+            ("symbol", symbol.to_string()),
+            ("side", side.to_string()),
+            ("type", "MARKET".to_string()),
+            ("quantity", format_quantity(symbol, quantity)),
+            ("reduceOnly", reduce_only.to_string()),
+            ("newOrderRespType", "RESULT".to_string()),
+        ]).await?;
+        Ok(serde_json::from_value(body)?)
+    }
+
+    pub async fn place_exit_order(&self, symbol: &Symbol, side: &str, kind: ExitKind, stop_price: f64, quantity: f64) -> Result<ExchangeOrder, Box<dyn Error + Send + Sync>> {
+        let body = self.signed_request(Method::POST, "/fapi/v1/order", &[
+            ("symbol", symbol.to_string()),
+            ("side", side.to_string()),
+            ("type", kind.order_type().to_string()),
+            ("stopPrice", format_price(symbol, stop_price)),
+            ("quantity", format_quantity(symbol, quantity)),
+            ("reduceOnly", "true".to_string()),
+            ("workingType", "CONTRACT_PRICE".to_string()),
+        ]).await?;
+        Ok(serde_json::from_value(body)?)
+    }
+
+    pub async fn get_order(&self, symbol: &Symbol, order_id: i64) -> Result<ExchangeOrder, Box<dyn Error + Send + Sync>> {
+        let body = self.signed_request(Method::GET, "/fapi/v1/order", &[
+            ("symbol", symbol.to_string()),
+            ("orderId", order_id.to_string()),
+        ]).await?;
+        Ok(serde_json::from_value(body)?)
+    }
+
+    pub async fn cancel_order(&self, symbol: &Symbol, order_id: i64) -> Result<ExchangeOrder, Box<dyn Error + Send + Sync>> {
+        let body = self.signed_request(Method::DELETE, "/fapi/v1/order", &[
+            ("symbol", symbol.to_string()),
+            ("orderId", order_id.to_string()),
+        ]).await?;
+        Ok(serde_json::from_value(body)?)
+    }
+
+    pub async fn create_listen_key(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.listen_key_request(Method::POST).await
+    }
+
+    pub async fn keep_alive_listen_key(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.listen_key_request(Method::PUT).await
+    }
+
+    async fn listen_key_request(&self, method: Method) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let res = self
+            .client
+            .request(method, format!("{}/fapi/v1/listenKey", self.base_url))
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send()
+            .await?;
+
+        let status = res.status();
+        let body: Value = res.json().await?;
+        if !status.is_success() {
+            return Err(format!("binance listenKey failed with {}: {}", status, body).into());
+        }
+        Ok(body["listenKey"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// Opens a live position: MARKET entry sized like a paper trade, then the protective exits.
+    ///
+    /// The bot books the actual fill price and quantity, the stop-loss and take-profit levels are
+    /// computed from the fill. Whatever filled but can't be booked is closed again, so no exchange
+    /// position is left without a bot. Exits that fail to be placed are retried by `sync_position`.
+    pub async fn open_position(&self, bot: &mut Bot, command: &OrderCommand, price: f64, now: DateTime<FixedOffset>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        bot.can_open_position()?;
+        let side = entry_side(command).ok_or("no side for order command")?;
+
+        let notional = (bot.capital - calculate_taker_fee(bot.capital)) * bot.leverage;
+        let mut quantity = calculate_buy_quantity(price, notional);
+        if let Some(info) = symbols::get(&bot.symbol) {
+            quantity = info.round_quantity(quantity);
+            info.check_notional(price, quantity)?;
+        }
+
+        let mut entry = self.place_market_order(&bot.symbol, side, quantity, false).await?;
+        if !entry.is_filled() {
+            entry = self.get_order(&bot.symbol, entry.order_id).await?;
+        }
+        if !entry.is_filled() {
+            if let Err(e) = self.cancel_order(&bot.symbol, entry.order_id).await {
+                warn!("failed to cancel entry {} of {}: {}", entry.order_id, bot.name, e);
+            }
+            let reason = format!("entry order {} not filled: {}", entry.order_id, entry.status);
+            let filled = entry.fill().quantity;
+            if filled > 0.0 {
+                return Err(self.flatten(&bot.symbol, command, filled, reason).await);
+            }
+            return Err(reason.into());
+        }
+
+        let fill = entry.fill();
+        if let Err(e) = bot.open_filled_position(command, fill.avg_price, fill.quantity, now) {
+            return Err(self.flatten(&bot.symbol, command, fill.quantity, e.to_string()).await);
+        }
+        bot.entry_order_id = entry.order_id;
+
+        info!("Live position opened: name: {}, order: {}, price: {}, qty: {}", bot.name, entry.order_id, fill.avg_price, fill.quantity);
+
+        // the position is the bot's now, it has to be saved as open even without its exits
+        if let Err(e) = self.place_missing_exits(bot).await {
+            error!("Failed to place exits for {}, retrying on the next sync: {}", bot.name, e);
+            bot.log = format!("exits not placed: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Closes `quantity` of an entry no bot could book, the error carries `reason` and the outcome.
+    async fn flatten(&self, symbol: &Symbol, command: &OrderCommand, quantity: f64, reason: String) -> Box<dyn Error + Send + Sync> {
+        let Some(side) = exit_side(command) else {
+            return reason.into();
+        };
+        match self.place_market_order(symbol, side, quantity, true).await {
+            Ok(order) => {
+                warn!("{}, closed {} {} again with order {}", reason, quantity, symbol, order.order_id);
+                format!("{}, the filled {} was closed again", reason, quantity).into()
+            }
+            Err(e) => {
+                error!("{}, closing {} {} failed, the position is not tracked: {}", reason, quantity, symbol, e);
+                format!("{}, closing the filled {} failed: {}", reason, quantity, e).into()
+            }
+        }
+    }
+
+    async fn place_missing_exits(&self, bot: &mut Bot) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let side = exit_side(&bot.order_type).ok_or("no side for order command")?;
+        // the trailing stop moves the level off the tick grid
+        if let Some(info) = symbols::get(&bot.symbol) {
+            bot.order_stop_loss = info.round_price(bot.order_stop_loss);
+        }
+
+        if bot.stop_order_id == 0 {
+            let order = self.place_exit_order(&bot.symbol, side, ExitKind::StopLoss, bot.order_stop_loss, bot.order_quantity).await?;
+            self.track(order.order_id);
+            bot.stop_order_id = order.order_id;
+        }
+        if bot.take_profit_order_id == 0 {
+            let order = self.place_exit_order(&bot.symbol, side, ExitKind::TakeProfit, bot.order_take_profit, bot.order_quantity).await?;
+            self.track(order.order_id);
+            bot.take_profit_order_id = order.order_id;
+        }
+        Ok(())
+    }
+
+    /// Reconciles a live position with the exchange, returns the closed `Order` once an exit filled.
+    pub async fn sync_position(&self, bot: &mut Bot, now: DateTime<FixedOffset>) -> Result<Option<Order>, Box<dyn Error + Send + Sync>> {
+        for (order_id, other_id) in [
+            (bot.stop_order_id, bot.take_profit_order_id),
+            (bot.take_profit_order_id, bot.stop_order_id),
+        ] {
+            if order_id == 0 {
+                continue;
+            }
+            let Some(fill) = self.find_fill(&bot.symbol, order_id).await? else {
+                continue;
+            };
+
+            if other_id != 0 {
+                if let Err(e) = self.cancel_order(&bot.symbol, other_id).await {
+                    warn!("failed to cancel order {} of {}: {}", other_id, bot.name, e);
+                }
+            }
+            self.forget(order_id);
+            self.forget(other_id);
+
+            let mut order = bot.close_position(fill.avg_price, now).map_err(|e| e.to_string())?;
+            order.exit_order_id = order_id;
+            info!("Live position closed: name: {}, order: {}, price: {}", bot.name, order_id, fill.avg_price);
+            return Ok(Some(order));
+        }
+
+        // protection that failed to be placed earlier
+        self.place_missing_exits(bot).await?;
+        Ok(None)
+    }
+
+    /// Moves the resting stop order to `bot.order_stop_loss` after the trailing stop shifted it.
+    pub async fn replace_stop_loss(&self, bot: &mut Bot) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if bot.stop_order_id == 0 {
+            return self.place_missing_exits(bot).await;
+        }
+        let side = exit_side(&bot.order_type).ok_or("no side for order command")?;
+        if let Some(info) = symbols::get(&bot.symbol) {
+            bot.order_stop_loss = info.round_price(bot.order_stop_loss);
+        }
+
+        // the old stop keeps protecting the position until the new one rests
+        let order = self.place_exit_order(&bot.symbol, side, ExitKind::StopLoss, bot.order_stop_loss, bot.order_quantity).await?;
+        self.track(order.order_id);
+        let old = std::mem::replace(&mut bot.stop_order_id, order.order_id);
+        self.forget(old);
+        if let Err(e) = self.cancel_order(&bot.symbol, old).await {
+            return Err(format!("moved the stop-loss to order {} but the old stop {} is still open: {}", order.order_id, old, e).into());
+        }
+        self.place_missing_exits(bot).await
+    }
+
+    async fn find_fill(&self, symbol: &Symbol, order_id: i64) -> Result<Option<OrderFill>, Box<dyn Error + Send + Sync>> {
+        if let Some(fill) = self.fills.lock().unwrap().remove(&order_id) {
+            return Ok(Some(fill));
+        }
+        let epoch = self.stream_epoch.load(Ordering::Relaxed);
+        if !self.rest_check_due(order_id, epoch) {
+            return Ok(None);
+        }
+
+        let order = self.get_order(symbol, order_id).await?;
+        self.tracked.lock().unwrap().insert(order_id, (epoch, Instant::now()));
+        Ok(order.is_filled().then(|| order.fill()))
+    }
+
+    fn rest_check_due(&self, order_id: i64, epoch: u64) -> bool {
+        if !self.stream_connected.load(Ordering::Relaxed) {
+            return true;
+        }
+        match self.tracked.lock().unwrap().get(&order_id) {
+            Some((checked_epoch, checked_at)) => *checked_epoch != epoch || checked_at.elapsed() >= REST_RECONCILE_INTERVAL,
+            None => true,
+        }
+    }
+
+    fn track(&self, order_id: i64) {
+        let epoch = self.stream_epoch.load(Ordering::Relaxed);
+        self.tracked.lock().unwrap().insert(order_id, (epoch, Instant::now()));
+    }
+
+    fn forget(&self, order_id: i64) {
+        self.tracked.lock().unwrap().remove(&order_id);
+        self.fills.lock().unwrap().remove(&order_id);
+    }
+
+    pub fn start_user_stream(self: Arc<Self>, ws_url: &str) -> tokio::task::JoinHandle<()> {
+        let ws_url = ws_url.trim_end_matches('/').to_string();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.listen_user_stream(&ws_url).await {
+                    error!("user data stream error: {}", e);
+                }
+                self.stream_connected.store(false, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        })
+    }
+
+    async fn listen_user_stream(&self, ws_url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let listen_key = self.create_listen_key().await?;
+        let (socket, _) = connect_async(format!("{}/ws/{}", ws_url, listen_key)).await?;
+        let (_, mut read) = socket.split();
+        self.stream_epoch.fetch_add(1, Ordering::Relaxed);
+        self.stream_connected.store(true, Ordering::Relaxed);
+        info!("user data stream connected");
+
+        let mut keepalive = tokio::time::interval(LISTEN_KEY_KEEPALIVE);
+        keepalive.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = keepalive.tick() => {
+                    self.keep_alive_listen_key().await?;
+                }
+                message = read.next() => {
+                    match message {
+                        Some(Ok(Message::Text(text))) => self.handle_user_event(text.as_str()),
+                        Some(Ok(Message::Close(_))) | None => return Ok(()),
+                        Some(Err(e)) => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_user_event(&self, text: &str) {
+        let Ok(event) = serde_json::from_str::<UserDataEvent>(text) else {
+            return;
+        };
+        if event.event != "ORDER_TRADE_UPDATE" {
+            return;
+        }
+        // entries and orders placed elsewhere are never asked for, keeping them would only grow the map
+        let tracked = self.tracked.lock().unwrap();
+        if let Some(order) = event.order.filter(|o| o.status == "FILLED" && tracked.contains_key(&o.order_id)) {
+            self.fills.lock().unwrap().insert(order.order_id, OrderFill {
+                avg_price: order.avg_price.parse().unwrap_or_default(),
+                quantity: order.filled_quantity.parse().unwrap_or_default(),
+            });
+        }
+    }
+}
+
+fn format_price(symbol: &Symbol, price: f64) -> String {
+    symbols::get(symbol).map_or_else(|| symbols::format_decimal(price), |info| info.format_price(price))
+}
+
+fn format_quantity(symbol: &Symbol, quantity: f64) -> String {
+    symbols::get(symbol).map_or_else(|| symbols::format_decimal(quantity), |info| info.format_quantity(quantity))
+}
+
+fn entry_side(command: &OrderCommand) -> Option<&'static str> {
+    match command {
+        OrderCommand::Long => Some("BUY"),
+        OrderCommand::Short => Some("SELL"),
+        OrderCommand::Wait => None,
+    }
+}
+
+fn exit_side(command: &OrderCommand) -> Option<&'static str> {
+    match command {
+        OrderCommand::Long => Some("SELL"),
+        OrderCommand::Short => Some("BUY"),
+        OrderCommand::Wait => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{BotMode, Timeframe};
+    use crate::tools;
+    use axum::extract::RawQuery;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    const API_KEY: &str = "key";
+    const SECRET: &str = "secret";
+
+    fn params(query: &str) -> HashMap<String, String> {
+        query
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn check_signature(headers: &HeaderMap, query: &str) -> Result<HashMap<String, String>, StatusCode> {
+        if headers.get("X-MBX-APIKEY").and_then(|v| v.to_str().ok()) != Some(API_KEY) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let (payload, signature) = query.rsplit_once("&signature=").ok_or(StatusCode::BAD_REQUEST)?;
+        if BinanceExecutor::new("", API_KEY, SECRET).sign(payload) != signature {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(params(payload))
+    }
+
+    // reduce-only MARKET orders sent to the mock, `(symbol, quantity)`
+    static CLOSED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+    // orders canceled on the mock, `(symbol, order id)`
+    static CANCELED: Mutex<Vec<(String, i64)>> = Mutex::new(Vec::new());
+
+    async fn new_order(headers: HeaderMap, RawQuery(query): RawQuery) -> Result<Json<Value>, StatusCode> {
+        let p = check_signature(&headers, &query.unwrap_or_default())?;
+        // XRPUSDT entries expire half filled, ADAUSDT entries fill less at a better price, BNBUSDT exits are rejected
+        let response = match (p["type"].as_str(), p["symbol"].as_str()) {
+            ("MARKET", "XRPUSDT") if p["reduceOnly"] == "false" => json!({"orderId": 4, "status": "NEW", "avgPrice": "0", "executedQty": "0"}),
+            ("MARKET", "ADAUSDT") if p["reduceOnly"] == "false" => json!({"orderId": 1, "status": "FILLED", "avgPrice": "99.0", "executedQty": "9"}),
+            ("STOP_MARKET" | "TAKE_PROFIT_MARKET", "BNBUSDT") => return Err(StatusCode::BAD_REQUEST),
+            // TRXUSDT stops at or above the price would trigger immediately, moved ones get a new id
+            ("STOP_MARKET", "TRXUSDT") if p["stopPrice"].parse::<f64>().unwrap() >= 100.0 => return Err(StatusCode::BAD_REQUEST),
+            ("STOP_MARKET", "TRXUSDT") if p["stopPrice"] != "99.6" => json!({"orderId": 6, "status": "NEW", "avgPrice": "0", "executedQty": "0"}),
+            ("MARKET", _) if p["reduceOnly"] == "true" => {
+                CLOSED.lock().unwrap().push((p["symbol"].clone(), p["quantity"].clone()));
+                json!({"orderId": 5, "status": "FILLED", "avgPrice": "100.0", "executedQty": p["quantity"]})
+            }
+            ("MARKET", _) => json!({"orderId": 1, "status": "FILLED", "avgPrice": "100.0", "executedQty": p["quantity"]}),
+            ("STOP_MARKET", _) if p["reduceOnly"] == "true" => json!({"orderId": 2, "status": "NEW", "avgPrice": "0", "executedQty": "0"}),
+            ("TAKE_PROFIT_MARKET", _) if p["reduceOnly"] == "true" => json!({"orderId": 3, "status": "NEW", "avgPrice": "0", "executedQty": "0"}),
+            _ => return Err(StatusCode::BAD_REQUEST),
+        };
+        Ok(Json(response))
+    }
+
+    async fn query_order(headers: HeaderMap, RawQuery(query): RawQuery) -> Result<Json<Value>, StatusCode> {
+        let p = check_signature(&headers, &query.unwrap_or_default())?;
+        // the stop-loss got hit
+        let response = match p["orderId"].as_str() {
+            "2" => json!({"orderId": 2, "status": "FILLED", "avgPrice": "99.5", "executedQty": "1"}),
+            "4" => json!({"orderId": 4, "status": "EXPIRED", "avgPrice": "100.0", "executedQty": "0.5"}),
+            id => json!({"orderId": id.parse::<i64>().unwrap(), "status": "NEW"}),
+        };
+        Ok(Json(response))
+    }
+
+    async fn cancel_order(headers: HeaderMap, RawQuery(query): RawQuery) -> Result<Json<Value>, StatusCode> {
+        let p = check_signature(&headers, &query.unwrap_or_default())?;
+        let order_id = p["orderId"].parse::<i64>().unwrap();
+        CANCELED.lock().unwrap().push((p["symbol"].clone(), order_id));
+        Ok(Json(json!({"orderId": order_id, "status": "CANCELED"})))
+    }
+
+    async fn listen_key(headers: HeaderMap) -> Result<Json<Value>, StatusCode> {
+        if headers.get("X-MBX-APIKEY").and_then(|v| v.to_str().ok()) != Some(API_KEY) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(Json(json!({"listenKey": "listen-key"})))
+    }
+
+    async fn mock_exchange() -> String {
+        let app = Router::new()
+            .route("/fapi/v1/order", post(new_order).get(query_order).delete(cancel_order))
+            .route("/fapi/v1/listenKey", post(listen_key).put(listen_key));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[test]
+    fn signs_like_binance_docs() {
+        let executor = BinanceExecutor::new("", "", "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j");
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        assert_eq!(executor.sign(query), "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71");
+    }
+
+    #[tokio::test]
+    async fn live_position_round_trip() {
+        let executor = BinanceExecutor::new(&mock_exchange().await, API_KEY, SECRET);
+        let now = tools::get_date(3);
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.mode = BotMode::Live;
+
+        executor.open_position(&mut bot, &OrderCommand::Long, 100.0, now).await.unwrap();
+        assert!(bot.in_pos);
+        assert_eq!((bot.entry_order_id, bot.stop_order_id, bot.take_profit_order_id), (1, 2, 3));
+        assert_eq!(bot.order_entry_price, 100.0);
+
+        let order = executor.sync_position(&mut bot, now).await.unwrap().expect("stop-loss filled");
+        assert_eq!(order.exit_price, 99.5);
+        assert_eq!((order.entry_order_id, order.exit_order_id), (1, 2));
+        assert!(!bot.in_pos);
+
+        assert_eq!(executor.create_listen_key().await.unwrap(), "listen-key");
+    }
+
+    #[tokio::test]
+    async fn positions_are_booked_from_the_fill() {
+        let executor = BinanceExecutor::new(&mock_exchange().await, API_KEY, SECRET);
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("ADAUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.mode = BotMode::Live;
+
+        // about 9.99 were asked for at 100
+        executor.open_position(&mut bot, &OrderCommand::Long, 100.0, tools::get_date(3)).await.unwrap();
+        let margin = 9.0 * 99.0 / 10.0;
+        assert_eq!((bot.order_entry_price, bot.order_quantity), (99.0, 9.0));
+        assert_eq!((bot.order_capital, bot.order_capital_with_leverage), (margin, margin * 10.0));
+        assert_eq!(bot.order_fee, calculate_taker_fee(margin));
+        assert_eq!(bot.capital, 100.0 - margin - calculate_taker_fee(margin));
+    }
+
+    #[tokio::test]
+    async fn stop_loss_is_replaced_before_the_old_one_is_canceled() {
+        let executor = BinanceExecutor::new(&mock_exchange().await, API_KEY, SECRET);
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("TRXUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.mode = BotMode::Live;
+        executor.open_position(&mut bot, &OrderCommand::Long, 100.0, tools::get_date(3)).await.unwrap();
+        assert_eq!((bot.order_stop_loss, bot.stop_order_id), (99.6, 2));
+        let trx_cancels = || CANCELED.lock().unwrap().iter().filter(|(s, _)| s == "TRXUSDT").map(|(_, id)| *id).collect::<Vec<_>>();
+
+        // the exchange refuses the new stop, the old one stays in place
+        bot.order_stop_loss = 100.5;
+        assert!(executor.replace_stop_loss(&mut bot).await.is_err());
+        assert_eq!(bot.stop_order_id, 2);
+        assert!(trx_cancels().is_empty());
+
+        bot.order_stop_loss = 99.8;
+        executor.replace_stop_loss(&mut bot).await.unwrap();
+        assert_eq!((bot.stop_order_id, bot.take_profit_order_id), (6, 3));
+        assert_eq!(trx_cancels(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn exits_are_checked_over_rest_after_a_reconnect() {
+        let executor = BinanceExecutor::new(&mock_exchange().await, API_KEY, SECRET);
+        let now = tools::get_date(3);
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.mode = BotMode::Live;
+        executor.open_position(&mut bot, &OrderCommand::Long, 100.0, now).await.unwrap();
+
+        // both exits were just checked while the stream is up, the stop fill event got lost
+        executor.stream_connected.store(true, Ordering::Relaxed);
+        assert!(executor.sync_position(&mut bot, now).await.unwrap().is_none());
+        assert!(bot.in_pos);
+
+        executor.stream_epoch.fetch_add(1, Ordering::Relaxed);
+        let order = executor.sync_position(&mut bot, now).await.unwrap().expect("stop-loss filled during the reconnect");
+        assert_eq!(order.exit_price, 99.5);
+        assert!(!bot.in_pos);
+        assert!(executor.tracked.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unbooked_fills_are_closed_and_failed_exits_keep_the_position() {
+        let executor = BinanceExecutor::new(&mock_exchange().await, API_KEY, SECRET);
+        let now = tools::get_date(3);
+
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("XRPUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.mode = BotMode::Live;
+        let err = executor.open_position(&mut bot, &OrderCommand::Long, 100.0, now).await.unwrap_err();
+        assert!(err.to_string().contains("closed again"), "{}", err);
+        assert!(!bot.in_pos);
+        assert!(CLOSED.lock().unwrap().contains(&("XRPUSDT".to_string(), "0.5".to_string())));
+
+        let mut bot = Bot::new(Timeframe::Min1, Symbol::new("BNBUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.mode = BotMode::Live;
+        executor.open_position(&mut bot, &OrderCommand::Long, 100.0, now).await.unwrap();
+        assert!(bot.in_pos);
+        assert_eq!((bot.entry_order_id, bot.stop_order_id, bot.take_profit_order_id), (1, 0, 0));
+    }
+
+    #[test]
+    fn only_fills_of_tracked_orders_are_kept() {
+        let executor = BinanceExecutor::new("", API_KEY, SECRET);
+        let event = |id: i64| json!({"e": "ORDER_TRADE_UPDATE", "o": {"i": id, "X": "FILLED", "ap": "99.5", "z": "1"}}).to_string();
+        executor.track(2);
+
+        executor.handle_user_event(&event(1));
+        executor.handle_user_event(&event(2));
+        assert_eq!(executor.fills.lock().unwrap().keys().copied().collect::<Vec<_>>(), vec![2]);
+
+        executor.forget(2);
+        assert!(executor.fills.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejected_signature_is_an_error() {
+        let executor = BinanceExecutor::new(&mock_exchange().await, API_KEY, "wrong");
+        assert!(executor.get_order(&Symbol::new("SOLUSDT"), 2).await.is_err());
+    }
+}
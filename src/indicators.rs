@@ -0,0 +1,397 @@
+use crate::models::models::{Bands, Candle, Dmi, HeikinAshi, Ichimoku, Macd, Stochastic, SuperTrend};
+use crate::ta;
+use crate::tools;
+use std::hash::{Hash, Hasher};
+
+/// An indicator with its settings.
+///
+/// Strategies list the specs they read and the `StrategyContainer` computes every distinct spec
+/// once per (timeframe, symbol), however many bots ask for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndicatorSpec {
+    /// EMA of the closes.
+    Ema { period: usize },
+    /// MACD of the closes, 12/26/9 is the usual setting.
+    Macd { fast: usize, slow: usize, signal: usize },
+    Stochastic { period_k: usize, smooth_k: usize, period_d: usize },
+    Rsi { period: usize },
+    Atr { period: usize },
+    /// ADX with the +DI and -DI it is built from.
+    Adx { period: usize },
+    SuperTrend { period: usize, multiplier: Factor },
+    /// Rolling VWAP over `period` bars.
+    Vwap { period: usize },
+    Obv,
+    Keltner { period: usize, atr_period: usize, multiplier: Factor },
+    Donchian { period: usize },
+    Ichimoku { conversion: usize, base: usize, span_b: usize, displacement: usize },
+    Psar { step: Factor, max: Factor },
+    Cci { period: usize },
+    WilliamsR { period: usize },
+    HeikinAshi,
+}
+
+/// A float setting of an `IndicatorSpec`, compared bit for bit so specs can be map keys.
+#[derive(Debug, Clone, Copy)]
+pub struct Factor(pub f64);
+
+impl PartialEq for Factor {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Factor {}
+
+impl Hash for Factor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// Output of an `IndicatorSpec`, every series is aligned with the candles it was computed from.
+#[derive(Debug, Clone)]
+pub enum Indicator {
+    Line(Vec<f64>),
+    Macd(Macd),
+    Stochastic(Stochastic),
+    Dmi(Dmi),
+    SuperTrend(SuperTrend),
+    Bands(Bands),
+    Ichimoku(Ichimoku),
+    HeikinAshi(HeikinAshi),
+}
+
+impl IndicatorSpec {
+    /// Batch computation over `candles`, what an `IndicatorStream` fed the same candles gives.
+    pub fn compute(&self, candles: &[Candle]) -> Indicator {
+        let closes = tools::get_close_prices(candles);
+        let highs = tools::get_high_prices(candles);
+        let lows = tools::get_low_prices(candles);
+        match *self {
+            IndicatorSpec::Ema { period } => Indicator::Line(ta::ema_slice(&closes, period)),
+            IndicatorSpec::Macd { fast, slow, signal } => {
+                let (macd, signal, histogram) = ta::macd_slice(&closes, fast, slow, signal);
+                Indicator::Macd(Macd { macd, signal, histogram })
+            }
+            IndicatorSpec::Stochastic { period_k, smooth_k, period_d } => {
+                let (k, d) = ta::stochastic_slice(&closes, &highs, &lows, period_k, smooth_k, period_d);
+                Indicator::Stochastic(Stochastic { k, d })
+            }
+            IndicatorSpec::Rsi { period } => Indicator::Line(ta::rsi_slice(&closes, period)),
+            IndicatorSpec::Atr { period } => Indicator::Line(ta::atr_slice(&highs, &lows, &closes, period)),
+            IndicatorSpec::Adx { period } => {
+                let (adx, plus_di, minus_di) = ta::adx_slice(&highs, &lows, &closes, period);
+                Indicator::Dmi(Dmi { adx, plus_di, minus_di })
+            }
+            IndicatorSpec::SuperTrend { period, multiplier } => {
+                let (line, direction) = ta::supertrend_slice(&highs, &lows, &closes, period, multiplier.0);
+                Indicator::SuperTrend(SuperTrend { line, direction })
+            }
+            IndicatorSpec::Vwap { period } => {
+                let volumes = tools::get_volume(candles);
+                Indicator::Line(ta::vwap_slice(&highs, &lows, &closes, &volumes, period))
+            }
+            IndicatorSpec::Obv => Indicator::Line(ta::obv_slice(&closes, &tools::get_volume(candles))),
+            IndicatorSpec::Keltner { period, atr_period, multiplier } => {
+                let (upper, middle, lower) = ta::keltner_slice(&highs, &lows, &closes, period, atr_period, multiplier.0);
+                Indicator::Bands(Bands { upper, middle, lower })
+            }
+            IndicatorSpec::Donchian { period } => {
+                let (upper, middle, lower) = ta::donchian_slice(&highs, &lows, period);
+                Indicator::Bands(Bands { upper, middle, lower })
+            }
+            IndicatorSpec::Ichimoku { conversion, base, span_b, displacement } => {
+                let [conversion, base, span_a, span_b, lagging] = ta::ichimoku_slice(&highs, &lows, &closes, conversion, base, span_b, displacement);
+                Indicator::Ichimoku(Ichimoku { conversion, base, span_a, span_b, lagging })
+            }
+            IndicatorSpec::Psar { step, max } => Indicator::Line(ta::psar_slice(&highs, &lows, step.0, max.0)),
+            IndicatorSpec::Cci { period } => Indicator::Line(ta::cci_slice(&highs, &lows, &closes, period)),
+            IndicatorSpec::WilliamsR { period } => Indicator::Line(ta::williams_r_slice(&highs, &lows, &closes, period)),
+            IndicatorSpec::HeikinAshi => {
+                let (open, high, low, close) = ta::heikin_ashi_slice(&tools::get_open_prices(candles), &highs, &lows, &closes);
+                Indicator::HeikinAshi(HeikinAshi { open, high, low, close })
+            }
+        }
+    }
+}
+
+impl Indicator {
+    fn empty(spec: &IndicatorSpec) -> Self {
+        spec.compute(&[])
+    }
+
+    /// Every series of the indicator, in declaration order.
+    pub fn lines(&self) -> Vec<&Vec<f64>> {
+        match self {
+            Indicator::Line(line) => vec![line],
+            Indicator::Macd(m) => vec![&m.macd, &m.signal, &m.histogram],
+            Indicator::Stochastic(s) => vec![&s.k, &s.d],
+            Indicator::Dmi(d) => vec![&d.adx, &d.plus_di, &d.minus_di],
+            Indicator::SuperTrend(s) => vec![&s.line, &s.direction],
+            Indicator::Bands(b) => vec![&b.upper, &b.middle, &b.lower],
+            Indicator::Ichimoku(i) => vec![&i.conversion, &i.base, &i.span_a, &i.span_b, &i.lagging],
+            Indicator::HeikinAshi(h) => vec![&h.open, &h.high, &h.low, &h.close],
+        }
+    }
+
+    fn lines_mut(&mut self) -> Vec<&mut Vec<f64>> {
+        match self {
+            Indicator::Line(line) => vec![line],
+            Indicator::Macd(m) => vec![&mut m.macd, &mut m.signal, &mut m.histogram],
+            Indicator::Stochastic(s) => vec![&mut s.k, &mut s.d],
+            Indicator::Dmi(d) => vec![&mut d.adx, &mut d.plus_di, &mut d.minus_di],
+            Indicator::SuperTrend(s) => vec![&mut s.line, &mut s.direction],
+            Indicator::Bands(b) => vec![&mut b.upper, &mut b.middle, &mut b.lower],
+            Indicator::Ichimoku(i) => vec![&mut i.conversion, &mut i.base, &mut i.span_a, &mut i.span_b, &mut i.lagging],
+            Indicator::HeikinAshi(h) => vec![&mut h.open, &mut h.high, &mut h.low, &mut h.close],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines()[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keeps the last `len` values of every series.
+    fn truncate_front(&mut self, len: usize) {
+        let excess = self.len().saturating_sub(len);
+        for line in self.lines_mut() {
+            line.drain(..excess);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum StreamState {
+    Ema(ta::EmaState),
+    Macd(ta::MacdState),
+    Stochastic(ta::StochasticState),
+    // no streaming version yet, recomputed over the window every bar
+    Batch,
+}
+
+/// An indicator kept up to date bar by bar instead of being recomputed over the whole window.
+///
+/// The output stays aligned with the candles of the last `update`.
+#[derive(Debug, Clone)]
+pub struct IndicatorStream {
+    spec: IndicatorSpec,
+    state: StreamState,
+    output: Indicator,
+    last_open_time: Option<u64>,
+}
+
+impl IndicatorStream {
+    pub fn new(spec: IndicatorSpec) -> Self {
+        let state = match spec {
+            IndicatorSpec::Ema { period } => StreamState::Ema(ta::EmaState::new(period)),
+            IndicatorSpec::Macd { fast, slow, signal } => StreamState::Macd(ta::MacdState::new(fast, slow, signal)),
+            IndicatorSpec::Stochastic { period_k, smooth_k, period_d } => {
+                StreamState::Stochastic(ta::StochasticState::new(period_k, smooth_k, period_d))
+            }
+            _ => StreamState::Batch,
+        };
+        Self {
+            output: Indicator::empty(&spec),
+            spec,
+            state,
+            last_open_time: None,
+        }
+    }
+
+    pub fn output(&self) -> &Indicator {
+        &self.output
+    }
+
+    /// Catches up with `candles`, sorted closed bars.
+    ///
+    /// When they continue the series seen so far only the new bars are fed, usually one. After a
+    /// gap, or when the window grew past what was fed, the state is rebuilt from all of them.
+    pub fn update(&mut self, candles: &[Candle]) {
+        if let StreamState::Batch = self.state {
+            self.output = self.spec.compute(candles);
+            return;
+        }
+
+        let fed = self.last_open_time.and_then(|last| {
+            let fed = candles.partition_point(|c| c.open_time <= last);
+            let seen = fed > 0 && candles[fed - 1].open_time == last;
+            // the output has to cover every candle up to the last one fed to stay aligned
+            (seen && self.output.len() >= fed).then_some(fed)
+        });
+
+        let new = match fed {
+            Some(fed) => &candles[fed..],
+            None => {
+                *self = Self::new(self.spec);
+                candles
+            }
+        };
+        for candle in new {
+            self.push(candle);
+        }
+        self.output.truncate_front(candles.len());
+    }
+
+    fn push(&mut self, candle: &Candle) {
+        match (&mut self.state, &mut self.output) {
+            (StreamState::Ema(state), Indicator::Line(line)) => line.push(state.next(candle.close)),
+            (StreamState::Macd(state), Indicator::Macd(out)) => {
+                let (macd, signal, histogram) = state.next(candle.close);
+                out.macd.push(macd);
+                out.signal.push(signal);
+                out.histogram.push(histogram);
+            }
+            (StreamState::Stochastic(state), Indicator::Stochastic(out)) => {
+                let (k, d) = state.next(candle.close, candle.high, candle.low);
+                out.k.push(k);
+                out.d.push(d);
+            }
+            _ => unreachable!("stream state and output are built from the same spec"),
+        }
+        self.last_open_time = Some(candle.open_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{Symbol, Timeframe};
+    use crate::models::models::StrategyContainer;
+    use crate::strategy::strategy::find_strategy;
+    use serde_json::{json, Value};
+
+    fn candles(n: usize) -> Vec<Candle> {
+        (0..n)
+            .map(|i| {
+                let close = 100.0 + (i as f64 / 7.0).sin() * 5.0;
+                Candle {
+                    close,
+                    open: close - 0.5,
+                    high: close + 1.0,
+                    low: close - 1.0,
+                    open_time: i as u64 * 60_000,
+                    close_time: i as u64 * 60_000 + 59_999,
+                    volume: 1.0,
+                    is_closed: true,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn container_computes_the_union_of_requirements() {
+        let (tf, symbol) = (Timeframe::Min5, Symbol::new("SOLUSDT"));
+        let mut sc = StrategyContainer::new();
+        for (name, params) in [("EmaMacd", Value::Null), ("EmaMacd2", Value::Null), ("EmaBounce", json!({"fast_ema": 20}))] {
+            sc.require(tf, symbol, &find_strategy(name, &params).unwrap().indicators());
+        }
+        sc.insert_candles(tf, symbol, candles(202));
+        sc.calculate_all();
+
+        // MACD and EMA 200 are shared, EMA 20 is only asked for once
+        assert_eq!(sc.indicator_count(), 3);
+        assert!(sc.get_macd(&tf, &symbol, 12, 26, 9).is_some());
+        assert!(sc.get_ema(&tf, &symbol, 20).is_some());
+        assert!(sc.get_ema(&tf, &symbol, 50).is_none());
+        assert!(sc.get_stochastic(&tf, &symbol, 14, 1, 3).is_none());
+
+        let expected = ta::ema_slice(&tools::get_close_prices(&candles(202)), 200);
+        assert_close(sc.get(&tf, &symbol, &IndicatorSpec::Ema { period: 200 }).unwrap(), &Indicator::Line(expected));
+
+        // requirements outlive the bar, indicators don't
+        sc.reset();
+        assert_eq!(sc.indicator_count(), 0);
+        sc.insert_candles(tf, symbol, candles(202));
+        sc.calculate_all();
+        assert_eq!(sc.indicator_count(), 3);
+    }
+
+    #[test]
+    fn streams_follow_a_sliding_window() {
+        let (tf, symbol) = (Timeframe::Min1, Symbol::new("SOLUSDT"));
+        let specs = [
+            IndicatorSpec::Ema { period: 50 },
+            IndicatorSpec::Macd { fast: 12, slow: 26, signal: 9 },
+            IndicatorSpec::Stochastic { period_k: 14, smooth_k: 3, period_d: 3 },
+        ];
+        let history = candles(400);
+        let mut sc = StrategyContainer::new();
+        sc.require(tf, symbol, &specs);
+
+        for end in 202..=history.len() {
+            sc.insert_candles(tf, symbol, history[end - 202..end].to_vec());
+            sc.calculate_all();
+            if end < history.len() {
+                sc.reset();
+            }
+        }
+
+        // fed bar by bar the streams carry the whole history, like a batch over all of it
+        for spec in specs {
+            let streamed = sc.get(&tf, &symbol, &spec).unwrap();
+            let batch = spec.compute(&history);
+            assert_eq!(streamed.len(), 202);
+            assert_close(streamed, &tail(batch, 202));
+        }
+
+        // a gap rebuilds from the window
+        sc.reset();
+        sc.insert_candles(tf, symbol, history[100..302].to_vec());
+        sc.calculate_all();
+        let rebuilt = sc.get(&tf, &symbol, &specs[0]).unwrap();
+        assert_close(rebuilt, &specs[0].compute(&history[100..302]));
+    }
+
+    #[test]
+    fn every_spec_is_aligned_with_its_candles() {
+        let (tf, symbol) = (Timeframe::Hour1, Symbol::new("ETHUSDT"));
+        let specs = [
+            IndicatorSpec::Rsi { period: 14 },
+            IndicatorSpec::Atr { period: 14 },
+            IndicatorSpec::Adx { period: 14 },
+            IndicatorSpec::SuperTrend { period: 10, multiplier: Factor(3.0) },
+            IndicatorSpec::Vwap { period: 20 },
+            IndicatorSpec::Obv,
+            IndicatorSpec::Keltner { period: 20, atr_period: 10, multiplier: Factor(2.0) },
+            IndicatorSpec::Donchian { period: 20 },
+            IndicatorSpec::Ichimoku { conversion: 9, base: 26, span_b: 52, displacement: 26 },
+            IndicatorSpec::Psar { step: Factor(0.02), max: Factor(0.2) },
+            IndicatorSpec::Cci { period: 20 },
+            IndicatorSpec::WilliamsR { period: 14 },
+            IndicatorSpec::HeikinAshi,
+        ];
+        let mut sc = StrategyContainer::new();
+        sc.require(tf, symbol, &specs);
+        sc.insert_candles(tf, symbol, candles(202));
+        sc.calculate_all();
+
+        for spec in specs {
+            let indicator = sc.get(&tf, &symbol, &spec).unwrap();
+            for line in indicator.lines() {
+                assert_eq!(line.len(), 202, "{:?}", spec);
+                // the Ichimoku lagging span ends 26 bars early
+                assert!(line[100..170].iter().all(|v| v.is_finite()), "{:?} has no value after warm-up", spec);
+            }
+        }
+        assert!(sc.get_line(&tf, &symbol, &IndicatorSpec::Rsi { period: 14 }).unwrap()[..14].iter().all(|v| v.is_nan()));
+        assert!(sc.get_line(&tf, &symbol, &IndicatorSpec::Adx { period: 14 }).is_none());
+    }
+
+    fn tail(mut indicator: Indicator, len: usize) -> Indicator {
+        indicator.truncate_front(len);
+        indicator
+    }
+
+    // running sums round differently from a fresh sum
+    fn assert_close(streamed: &Indicator, batch: &Indicator) {
+        for (s, b) in streamed.lines().into_iter().zip(batch.lines()) {
+            assert_eq!(s.len(), b.len());
+            assert!(s.iter().zip(b).all(|(s, b)| s.is_nan() && b.is_nan() || (s - b).abs() < 1e-9), "{:?} != {:?}", s, b);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+pub mod api;
+pub mod backtest;
+pub mod calculator;
+pub mod config;
+pub mod connector;
+pub mod entry_manager;
+pub mod enums;
+pub mod executor;
+pub mod indicators;
+pub mod logger;
+pub mod market_stream;
+pub mod models;
+pub mod persistence;
+pub mod position_manager;
+pub mod replay_connector;
+pub mod repository;
+pub mod strategy;
+pub mod supervisor;
+pub mod symbols;
+pub mod ta;
+pub mod tools;
+pub mod constants;
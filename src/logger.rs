@@ -1,3 +1,4 @@
+use crate::config;
 use chrono::{FixedOffset, Utc};
 use env_logger::fmt::style::{Color, RgbColor};
 use env_logger::Builder;
@@ -30,7 +31,7 @@ pub fn init_logger() {
             let file = record.file().unwrap_or("<unknown>");
             let line = record.line().unwrap_or(0);
 
-            let offset = FixedOffset::east_opt(3 * 60 * 60).unwrap(); // +3 utc
+            let offset = FixedOffset::east_opt(config::get().time_zone * 60 * 60).unwrap();
             let now = Utc::now().with_timezone(&offset).format("%H:%M:%S %d/%m");
 
             let mut abs_path = project_root.clone();
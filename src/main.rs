@@ -1,118 +1,182 @@
-mod api;
-mod calculator;
-mod connector;
-mod entry_manager;
-mod enums;
-mod logger;
-mod models;
-mod position_manager;
-mod repository;
-mod strategy;
-mod ta;
-mod tools;
-mod constants;
-
-use crate::api::get_router;
-use crate::connector::BinanceConnector;
-use crate::entry_manager::EntryManager;
-use crate::enums::Symbol::{BnbUsdt, BtcUsdt, EthUsdt, SolUsdt};
-use crate::enums::Timeframe::{Hour1, Hour4, Min1, Min15, Min30, Min5};
-use crate::logger::init_logger;
-use crate::models::bot::Bot;
-use crate::models::models::{Container, SharedVec};
-use crate::position_manager::PositionManager;
-use crate::repository::Repository;
-use log::info;
-use std::cell::UnsafeCell;
-use std::collections::HashMap;
-use std::env::home_dir;
+use traderrs::api::get_router;
+use traderrs::config;
+use traderrs::config::{Config, DEFAULT_CONFIG_PATH};
+use traderrs::connector::{BinanceConnector, MarketDataSource};
+use traderrs::entry_manager::EntryManager;
+use traderrs::executor::BinanceExecutor;
+use traderrs::logger::init_logger;
+use traderrs::market_stream::{MarketStream, BINANCE_FUTURES_WS_URL};
+use traderrs::models::bot::Bot;
+use traderrs::models::bot_registry::BotRegistry;
+use traderrs::models::models::Container;
+use traderrs::persistence;
+use traderrs::position_manager::PositionManager;
+use traderrs::repository::Repository;
+use traderrs::supervisor::Supervisor;
+use traderrs::symbols;
+use log::{error, info};
+use std::error::Error;
 use std::path::PathBuf;
+use std::process::exit;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 
+// managers finish the tick they are in, well within systemd's TimeoutStopSec
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(20);
+
 #[tokio::main]
 async fn main() {
+    // before anything reads `config::get()`, the logger included
+    let config_path = std::env::var("TRADERRS_CONFIG").unwrap_or(DEFAULT_CONFIG_PATH.to_string());
+    let config = match Config::load(&PathBuf::from(&config_path)) {
+        Ok(config) => config::init(config),
+        Err(e) => {
+            eprintln!("failed to load {}: {}", config_path, e);
+            exit(1);
+        }
+    };
+
     #[cfg(debug_assertions)]
     init_logger();
 
-    let (bots, c) = init_dependencies();
+    symbols::load(config).await;
 
-    let app = get_router(bots, c);
+    let (bots, c, supervisor) = init_dependencies().await;
 
-    let listener = TcpListener::bind("0.0.0.0:3030").await.unwrap();
+    let app = get_router(bots.clone(), c.clone(), supervisor.clone());
+
+    let listener = TcpListener::bind(&config.server.listen).await.unwrap();
     info!(
         "listening on port: {}",
         listener.local_addr().unwrap().port()
     );
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // nothing changes the bots once the managers have stopped, this snapshot is final
+    supervisor.shutdown(SHUTDOWN_TIMEOUT).await;
+
+    match persistence::save_snapshot(&bots, &c).await {
+        Ok(count) => info!("saved state of {} bots, exiting", count),
+        Err(e) => {
+            error!("failed to save bot state on shutdown: {}", e);
+            exit(1);
+        }
+    }
 }
 
-fn init_dependencies() -> (Arc<SharedVec<Bot>>, Arc<Container>) {
-    let r = get_repository().expect("Error creating repository");
-    let c = Arc::new(Container { repository: r });
+/// Ctrl-C, or SIGTERM from `systemctl stop` and redeploys.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("failed to listen for ctrl-c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("shutting down");
+}
+
+async fn init_dependencies() -> (Arc<BotRegistry>, Arc<Container>, Arc<Supervisor>) {
+    let r = match get_repository() {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("failed to open the database: {}", e);
+            exit(1);
+        }
+    };
+    let executor = BinanceExecutor::from_env().map(Arc::new);
+    if let Some(executor) = executor.as_ref() {
+        Arc::clone(executor).start_user_stream(BINANCE_FUTURES_WS_URL);
+    }
 
-    let mut bots_from_db = c
-        .repository
+    let mut bots_from_db = r
         .get_bot_state()
+        .await
         .expect("error getting bot state");
 
-    let mut bots_name_map = HashMap::new();
-    for b in bots_from_db.iter() {
-        bots_name_map.insert(b.name.clone(), {});
+    // bots of the config matrix are added once, afterwards they are managed through the API
+    let seeded = r.seed_bots(init_bots())
+        .await
+        .expect("error seeding bots");
+    if !seeded.is_empty() {
+        info!("added {} default bots", seeded.len());
     }
+    bots_from_db.extend(seeded);
 
-    let new_bots = init_bots();
-
-    for b in new_bots.into_iter() {
-        if !bots_name_map.contains_key(&b.name) {
-            bots_from_db.push(b);
+    let mut subscriptions = Vec::new();
+    for b in bots_from_db.iter() {
+        if !subscriptions.contains(&(b.symbol, b.timeframe)) {
+            subscriptions.push((b.symbol, b.timeframe));
         }
     }
 
-    let bots = Arc::new(SharedVec(UnsafeCell::new(bots_from_db)));
+    // positions left open by the last run are tracked again from where they were saved
+    let open_positions = bots_from_db.iter().filter(|b| b.in_pos).count();
+    if open_positions > 0 {
+        info!("resuming {} open positions", open_positions);
+    }
+
+    let bots = Arc::new(BotRegistry::new(bots_from_db));
+
+    let rest: Arc<dyn MarketDataSource> = Arc::new(BinanceConnector::new());
+    let stream = Arc::new(MarketStream::new(BINANCE_FUTURES_WS_URL, rest, subscriptions));
+    Arc::clone(&stream).start();
+    let c = Arc::new(Container { repository: r, executor, market_stream: Some(Arc::clone(&stream)) });
 
-    let connector = BinanceConnector::new();
-    let mut position_manager =
-        PositionManager::new(bots.clone(), Arc::new(connector.clone()), c.clone());
-    let mut entry_manager = EntryManager::new(bots.clone(), Arc::new(connector), Arc::clone(&c));
+    let connector: Arc<dyn MarketDataSource> = stream;
+    let supervisor = Arc::new(Supervisor::new());
 
-    tokio::spawn(async move {
-        position_manager.start().await;
+    // a restarted manager starts from scratch, the bots and their positions live in the registry
+    let (b, con, cont) = (bots.clone(), Arc::clone(&connector), c.clone());
+    supervisor.spawn("position_manager", move |token| {
+        let mut position_manager = PositionManager::new(b.clone(), Arc::clone(&con), cont.clone());
+        async move { position_manager.start(token).await }
     });
 
-    tokio::spawn(async move {
-        entry_manager.start().await;
+    let (b, con, cont) = (bots.clone(), connector, c.clone());
+    supervisor.spawn("entry_manager", move |token| {
+        let mut entry_manager = EntryManager::new(b.clone(), Arc::clone(&con), cont.clone());
+        async move { entry_manager.start(token).await }
     });
 
-    (bots, c)
+    let (b, cont) = (bots.clone(), c.clone());
+    supervisor.spawn("snapshots", move |token| persistence::run_snapshots(b.clone(), cont.clone(), token));
+
+    (bots, c, supervisor)
 }
 
 fn init_bots() -> Vec<Bot> {
     let mut bots = Vec::new();
-    let capital = 100.0;
-    let leverage = 10.0;
-    let take_profit_ratio = 0.8;
-    let stop_loss_ratio = 0.4;
-    let trailing_stop_activation_point = 0.1;
-
-    let tf = [Min1, Min5, Min15, Min30, Hour1, Hour4];
-    let st = ["EmaMacd", "EmaMacd2", "EmaBounce", "StocBorder"];
-    //let st = ["StocBorder"];
-
-    let smb = [SolUsdt, EthUsdt, BnbUsdt, BtcUsdt];
+    let config = config::get();
+    let risk = &config.risk;
 
-    for t in tf.iter() {
-        for s in st.iter() {
-            for symbol in smb.iter() {
+    for t in config.bots.timeframes.iter() {
+        for s in config.bots.strategies.iter() {
+            for symbol in config.bots.symbols.iter() {
                 let bot = Bot::new(
                     *t,
                     *symbol,
                     s.to_string(),
-                    capital,
-                    leverage,
-                    take_profit_ratio,
-                    stop_loss_ratio,
-                    trailing_stop_activation_point,
+                    risk.capital,
+                    risk.leverage,
+                    risk.take_profit_ratio,
+                    risk.stop_loss_ratio,
+                    risk.trailing_stop_activation_point,
                 );
                 bots.push(bot);
             }
@@ -122,13 +186,12 @@ fn init_bots() -> Vec<Bot> {
     bots
 }
 
-fn get_repository() -> rusqlite::Result<Repository> {
-    let mut path = PathBuf::from(home_dir().unwrap());
-    path.push("db");
+fn get_repository() -> Result<Repository, Box<dyn Error + Send + Sync>> {
+    let path = config::get().database_path();
 
-    std::fs::create_dir_all(&path).unwrap();
-
-    path.push("traders_db.sqlite");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
 
     Repository::new(path)
 }
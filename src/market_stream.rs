@@ -0,0 +1,524 @@
+use crate::connector::MarketDataSource;
+use crate::enums::{Symbol, Timeframe};
+use crate::models::models::{Candle, PriceRange};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+pub const BINANCE_FUTURES_WS_URL: &str = "wss://fstream.binance.com";
+
+// klines kept per (timeframe, symbol), enough for the 202 the EntryManager asks for
+const CANDLES_CACHE_LIMIT: usize = 500;
+const BACKFILL_LIMIT: i32 = 202;
+// a cached price older than this is considered stale and fetched over REST instead
+const PRICE_MAX_AGE: Duration = Duration::from_secs(10);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct StreamEnvelope {
+    data: StreamEvent,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "e")]
+enum StreamEvent {
+    #[serde(rename = "markPriceUpdate")]
+    MarkPrice {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "p")]
+        price: String,
+    },
+    #[serde(rename = "kline")]
+    Kline {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "k")]
+        kline: KlineEvent,
+    },
+}
+
+#[derive(Deserialize)]
+struct KlineEvent {
+    #[serde(rename = "t")]
+    open_time: u64,
+    #[serde(rename = "T")]
+    close_time: u64,
+    #[serde(rename = "i")]
+    interval: String,
+    #[serde(rename = "o")]
+    open: String,
+    #[serde(rename = "h")]
+    high: String,
+    #[serde(rename = "l")]
+    low: String,
+    #[serde(rename = "c")]
+    close: String,
+    #[serde(rename = "v")]
+    volume: String,
+    #[serde(rename = "x")]
+    is_closed: bool,
+}
+
+#[derive(Default)]
+struct MarketCache {
+    prices: HashMap<Symbol, (f64, Instant)>,
+    candles: HashMap<(Timeframe, Symbol), Vec<Candle>>,
+    // traded extremes since the last `take_price_range`
+    ranges: HashMap<Symbol, PriceRange>,
+}
+
+/// Keeps prices and klines up to date from the Binance futures combined stream.
+///
+/// Subscribes to `<symbol>@markPrice@1s` and `<symbol>@kline_<tf>` for every pair, reconnects
+/// with backoff when the socket drops and backfills the klines over REST after every (re)connect
+/// or detected gap. Reads fall back to the REST source while the cache can't serve them.
+/// `subscribe` adds a pair at runtime, the socket reconnects with the new stream list.
+pub struct MarketStream {
+    ws_url: String,
+    rest: Arc<dyn MarketDataSource>,
+    subscriptions: RwLock<Vec<(Symbol, Timeframe)>>,
+    // wakes the listener to reconnect with a changed stream list
+    resubscribe: Notify,
+    cache: RwLock<MarketCache>,
+    connected: AtomicBool,
+    connections: AtomicU64,
+}
+
+impl Debug for MarketStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MarketStream")
+         .field("ws_url", &self.ws_url)
+         .field("subscriptions", &self.subscriptions())
+         .finish()
+    }
+}
+
+impl MarketStream {
+    pub fn new(ws_url: &str, rest: Arc<dyn MarketDataSource>, subscriptions: Vec<(Symbol, Timeframe)>) -> Self {
+        Self {
+            ws_url: ws_url.trim_end_matches('/').to_string(),
+            rest,
+            subscriptions: RwLock::new(subscriptions),
+            resubscribe: Notify::new(),
+            cache: RwLock::new(MarketCache::default()),
+            connected: AtomicBool::new(false),
+            connections: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Number of successful connections so far, reconnects included.
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Adds the streams of a pair, `false` when it was already subscribed.
+    pub fn subscribe(&self, symbol: Symbol, timeframe: Timeframe) -> bool {
+        {
+            let mut subscriptions = self.subscriptions.write().unwrap();
+            if subscriptions.contains(&(symbol, timeframe)) {
+                return false;
+            }
+            subscriptions.push((symbol, timeframe));
+        }
+        info!("subscribing to {:?} {:?}", symbol, timeframe);
+        self.resubscribe.notify_one();
+        true
+    }
+
+    pub fn subscriptions(&self) -> Vec<(Symbol, Timeframe)> {
+        self.subscriptions.read().unwrap().clone()
+    }
+
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            self.run().await;
+        })
+    }
+
+    async fn run(&self) {
+        let mut delay = Duration::from_secs(1);
+
+        loop {
+            match self.connect_and_listen().await {
+                Ok(()) => {
+                    warn!("market stream closed, reconnecting");
+                    delay = Duration::from_secs(1);
+                }
+                Err(e) => error!("market stream error: {}, reconnecting in {:?}", e, delay),
+            }
+            self.connected.store(false, Ordering::Relaxed);
+
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    fn stream_url(&self, subscriptions: &[(Symbol, Timeframe)]) -> String {
+        let mut streams: Vec<String> = Vec::new();
+        for (symbol, timeframe) in subscriptions.iter() {
+            let s = symbol.to_string().to_lowercase();
+            let price_stream = format!("{}@markPrice@1s", s);
+            if !streams.contains(&price_stream) {
+                streams.push(price_stream);
+            }
+            streams.push(format!("{}@kline_{}", s, timeframe));
+        }
+        format!("{}/stream?streams={}", self.ws_url, streams.join("/"))
+    }
+
+    async fn connect_and_listen(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let subscriptions = self.subscriptions();
+        let (socket, _) = connect_async(self.stream_url(&subscriptions)).await?;
+        let (_, mut read) = socket.split();
+
+        self.connected.store(true, Ordering::Relaxed);
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        info!("market stream connected, {} subscriptions", subscriptions.len());
+
+        // anything that happened while disconnected only exists on REST
+        for (symbol, timeframe) in subscriptions.iter() {
+            self.backfill(*symbol, *timeframe).await;
+        }
+
+        loop {
+            tokio::select! {
+                _ = self.resubscribe.notified() => return Ok(()),
+                message = read.next() => match message {
+                    Some(message) => match message? {
+                        Message::Text(text) => self.handle_message(text.as_str()).await,
+                        Message::Close(_) => return Ok(()),
+                        _ => {}
+                    },
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    async fn handle_message(&self, text: &str) {
+        let event = match serde_json::from_str::<StreamEnvelope>(text) {
+            Ok(envelope) => envelope.data,
+            Err(e) => {
+                debug!("skipping stream message: {}", e);
+                return;
+            }
+        };
+
+        match event {
+            StreamEvent::MarkPrice { symbol, price } => {
+                let (Ok(symbol), Ok(price)) = (Symbol::from_str(&symbol), price.parse::<f64>()) else {
+                    return;
+                };
+                self.cache.write().unwrap().prices.insert(symbol, (price, Instant::now()));
+            }
+            StreamEvent::Kline { symbol, kline } => {
+                let (Ok(symbol), Ok(timeframe)) = (Symbol::from_str(&symbol), Timeframe::from_str(&kline.interval)) else {
+                    return;
+                };
+                let candle = Candle {
+                    close: kline.close.parse().unwrap_or_default(),
+                    open: kline.open.parse().unwrap_or_default(),
+                    high: kline.high.parse().unwrap_or_default(),
+                    low: kline.low.parse().unwrap_or_default(),
+                    open_time: kline.open_time,
+                    close_time: kline.close_time,
+                    volume: kline.volume.parse().unwrap_or_default(),
+                    is_closed: kline.is_closed,
+                };
+
+                if !self.push_candle(symbol, timeframe, candle) {
+                    warn!("kline gap for {:?} {:?}, backfilling", symbol, timeframe);
+                    self.backfill(symbol, timeframe).await;
+                }
+            }
+        }
+    }
+
+    /// Returns `false` when the candle doesn't follow the cached series.
+    fn push_candle(&self, symbol: Symbol, timeframe: Timeframe, candle: Candle) -> bool {
+        let mut cache = self.cache.write().unwrap();
+        let cache = &mut *cache;
+        let candles = cache.candles.entry((timeframe, symbol)).or_default();
+
+        // a kline high/low that moved since the previous event was traded in between
+        let range = cache.ranges.entry(symbol).or_insert(PriceRange::from_price(candle.close));
+        match candles.last() {
+            Some(last) if last.open_time == candle.open_time => {
+                if candle.high > last.high {
+                    range.update(candle.high);
+                }
+                if candle.low < last.low {
+                    range.update(candle.low);
+                }
+            }
+            _ => {
+                range.update(candle.high);
+                range.update(candle.low);
+            }
+        }
+        range.update(candle.close);
+
+        match candles.last() {
+            Some(last) if last.open_time == candle.open_time => {
+                *candles.last_mut().unwrap() = candle;
+            }
+            Some(last) if last.open_time + timeframe.duration_ms() == candle.open_time => {
+                candles.push(candle);
+            }
+            Some(last) if last.open_time > candle.open_time => {}
+            Some(_) => return false,
+            None => candles.push(candle),
+        }
+
+        if candles.len() > CANDLES_CACHE_LIMIT {
+            let excess = candles.len() - CANDLES_CACHE_LIMIT;
+            candles.drain(..excess);
+        }
+        true
+    }
+
+    async fn backfill(&self, symbol: Symbol, timeframe: Timeframe) {
+        match self.rest.get_candles(symbol, timeframe, BACKFILL_LIMIT).await {
+            Ok(fetched) => self.merge_candles(symbol, timeframe, fetched),
+            Err(e) => error!("backfill failed for {:?} {:?}: {}", symbol, timeframe, e),
+        }
+    }
+
+    fn merge_candles(&self, symbol: Symbol, timeframe: Timeframe, fetched: Vec<Candle>) {
+        let mut cache = self.cache.write().unwrap();
+        let candles = cache.candles.entry((timeframe, symbol)).or_default();
+
+        // streamed candles newer than the REST snapshot are kept
+        let newer: Vec<Candle> = match fetched.last() {
+            Some(last) => candles.drain(..).filter(|c| c.open_time > last.open_time).collect(),
+            None => return,
+        };
+        *candles = fetched;
+        candles.extend(newer);
+    }
+}
+
+#[async_trait]
+impl MarketDataSource for MarketStream {
+    async fn get_price(&self, symbol: &Symbol) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        if self.is_connected() {
+            if let Some((price, updated)) = self.cache.read().unwrap().prices.get(symbol) {
+                if updated.elapsed() < PRICE_MAX_AGE {
+                    return Ok(*price);
+                }
+            }
+        }
+
+        self.rest.get_price(symbol).await
+    }
+
+    async fn get_candles(
+        &self,
+        symbol: Symbol,
+        timeframe: Timeframe,
+        limit: i32,
+    ) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+        let limit = limit.max(0) as usize;
+        if self.is_connected() {
+            let cache = self.cache.read().unwrap();
+            if let Some(candles) = cache.candles.get(&(timeframe, symbol)) {
+                if candles.len() >= limit {
+                    return Ok(candles[candles.len() - limit..].to_vec());
+                }
+            }
+        }
+
+        let fetched = self.rest.get_candles(symbol, timeframe, limit as i32).await?;
+        if self.subscriptions.read().unwrap().contains(&(symbol, timeframe)) {
+            self.merge_candles(symbol, timeframe, fetched.clone());
+        }
+        Ok(fetched)
+    }
+
+    async fn take_price_range(&self, symbol: &Symbol) -> Result<PriceRange, Box<dyn Error + Send + Sync>> {
+        if self.is_connected() {
+            let mut cache = self.cache.write().unwrap();
+            if let Some(range) = cache.ranges.get_mut(symbol) {
+                let taken = *range;
+                *range = PriceRange::from_price(taken.last);
+                return Ok(taken);
+            }
+        }
+
+        Ok(PriceRange::from_price(self.get_price(symbol).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::SinkExt;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    struct StubSource {
+        calls: AtomicU64,
+    }
+
+    #[async_trait]
+    impl MarketDataSource for StubSource {
+        async fn get_price(&self, _symbol: &Symbol) -> Result<f64, Box<dyn Error + Send + Sync>> {
+            Ok(1.0)
+        }
+
+        async fn get_candles(&self, _symbol: Symbol, _timeframe: Timeframe, _limit: i32) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            Ok((0..3).map(|i| candle(i * 60_000, 10.0 + i as f64)).collect())
+        }
+    }
+
+    fn candle(open_time: u64, close: f64) -> Candle {
+        Candle { close, open: close, high: close, low: close, open_time, close_time: open_time + 59_999, volume: 1.0, is_closed: true }
+    }
+
+    fn kline_message(open_time: u64, close: f64) -> Message {
+        Message::text(format!(
+            r#"{{"stream":"solusdt@kline_1m","data":{{"e":"kline","E":1,"s":"SOLUSDT","k":{{"t":{},"T":{},"s":"SOLUSDT","i":"1m","o":"{c}","c":"{c}","h":"{c}","l":"{c}","v":"1","x":false}}}}}}"#,
+            open_time,
+            open_time + 59_999,
+            c = close
+        ))
+    }
+
+    fn price_message(price: f64) -> Message {
+        Message::text(format!(
+            r#"{{"stream":"solusdt@markPrice@1s","data":{{"e":"markPriceUpdate","E":1,"s":"SOLUSDT","p":"{}"}}}}"#,
+            price
+        ))
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn streams_prices_and_candles_and_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            // first connection: one price, one new candle, then drop the socket
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(price_message(101.5)).await.unwrap();
+            ws.send(kline_message(180_000, 13.0)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            drop(ws);
+
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(price_message(99.0)).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let rest = Arc::new(StubSource { calls: AtomicU64::new(0) });
+        let stream = Arc::new(MarketStream::new(&url, rest.clone(), vec![(Symbol::new("SOLUSDT"), Timeframe::Min1)]));
+        Arc::clone(&stream).start();
+
+        wait_for(|| stream.cache.read().unwrap().prices.contains_key(&Symbol::new("SOLUSDT"))).await;
+        assert_eq!(stream.get_price(&Symbol::new("SOLUSDT")).await.unwrap(), 101.5);
+
+        wait_for(|| stream.cache.read().unwrap().candles.get(&(Timeframe::Min1, Symbol::new("SOLUSDT"))).map(|c| c.len()) == Some(4)).await;
+        let candles = stream.get_candles(Symbol::new("SOLUSDT"), Timeframe::Min1, 4).await.unwrap();
+        assert_eq!(candles.last().unwrap().close, 13.0);
+
+        wait_for(|| stream.connections() == 2 && stream.is_connected()).await;
+        wait_for(|| stream.cache.read().unwrap().prices.get(&Symbol::new("SOLUSDT")).map(|p| p.0) == Some(99.0)).await;
+        // backfilled once per connection
+        assert_eq!(rest.calls.load(Ordering::Relaxed), 2);
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn subscribing_reconnects_with_the_new_streams() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            loop {
+                let (tcp, _) = listener.accept().await.unwrap();
+                sockets.push(accept_async(tcp).await.unwrap());
+            }
+        });
+
+        let rest = Arc::new(StubSource { calls: AtomicU64::new(0) });
+        let stream = Arc::new(MarketStream::new(&url, rest.clone(), vec![(Symbol::new("SOLUSDT"), Timeframe::Min1)]));
+        Arc::clone(&stream).start();
+        wait_for(|| stream.connections() == 1).await;
+
+        assert!(stream.subscribe(Symbol::new("ETHUSDT"), Timeframe::Hour1));
+        assert!(!stream.subscribe(Symbol::new("ETHUSDT"), Timeframe::Hour1));
+        wait_for(|| stream.connections() == 2).await;
+        let url = stream.stream_url(&stream.subscriptions());
+        assert!(url.contains("ethusdt@kline_1h") && url.contains("solusdt@kline_1m"), "{}", url);
+        // both pairs are backfilled on the new connection
+        wait_for(|| rest.calls.load(Ordering::Relaxed) == 3).await;
+
+        server.abort();
+    }
+
+    #[test]
+    fn gap_in_klines_is_reported() {
+        let rest = Arc::new(StubSource { calls: AtomicU64::new(0) });
+        let stream = MarketStream::new("ws://unused", rest, vec![]);
+
+        assert!(stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, candle(0, 1.0)));
+        assert!(stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, candle(0, 2.0)));
+        assert!(stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, candle(60_000, 3.0)));
+        assert!(!stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, candle(240_000, 4.0)));
+    }
+
+    #[tokio::test]
+    async fn price_range_tracks_kline_extremes_between_takes() {
+        let rest = Arc::new(StubSource { calls: AtomicU64::new(0) });
+        let stream = MarketStream::new("ws://unused", rest, vec![]);
+        stream.connected.store(true, Ordering::Relaxed);
+
+        let mut c = candle(0, 10.0);
+        stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, c.clone());
+        stream.take_price_range(&Symbol::new("SOLUSDT")).await.unwrap();
+
+        c.high = 12.0;
+        c.close = 11.0;
+        stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, c.clone());
+        c.low = 9.5;
+        c.close = 10.5;
+        stream.push_candle(Symbol::new("SOLUSDT"), Timeframe::Min1, c.clone());
+
+        let range = stream.take_price_range(&Symbol::new("SOLUSDT")).await.unwrap();
+        assert_eq!(range, PriceRange { high: 12.0, low: 9.5, last: 10.5 });
+
+        // the wick is not seen again by the next take
+        let range = stream.take_price_range(&Symbol::new("SOLUSDT")).await.unwrap();
+        assert_eq!(range, PriceRange::from_price(10.5));
+    }
+}
@@ -2,19 +2,20 @@ use crate::calculator::{
     calculate_buy_quantity, calculate_maker_fee, calculate_pnl, calculate_roe, calculate_stop_loss,
     calculate_take_profit, calculate_taker_fee,
 };
-use crate::connector::BinanceConnector;
-use crate::constants::MIN_CAPITAL_TO_STOP;
-use crate::enums::Symbol::SolUsdt;
+use crate::connector::MarketDataSource;
+use crate::config;
 use crate::enums::Timeframe::Min1;
-use crate::enums::{OrderCommand, Symbol, Timeframe};
+use crate::enums::{BotMode, OrderCommand, Symbol, Timeframe};
 use crate::models::models::{Order, StrategyContainer};
 use crate::strategy::strategy;
 use crate::strategy::strategy::Strategy;
+use crate::symbols;
 use crate::tools;
 use crate::tools::is_timeframe_now;
-use chrono::{DateTime, FixedOffset, Timelike, Utc};
-use log::{debug, info};
+use chrono::{DateTime, FixedOffset, Timelike};
+use log::{debug, error, info};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::error::Error;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
@@ -25,11 +26,16 @@ pub struct Bot {
     pub symbol: Symbol,
     pub timeframe: Timeframe,
     pub strategy_name: String,
+    /// Settings of the strategy with defaults filled in, see `StrategyParams`.
+    #[serde(default)]
+    pub strategy_params: Value,
     #[serde(skip)]
     pub strategy: Option<Arc<dyn Strategy + Send + Sync>>,
     pub capital: f64,
     pub group: String,
     pub is_not_active: bool,
+    #[serde(default)]
+    pub mode: BotMode,
 
     pub wins: i16,
     pub losses: i16,
@@ -57,6 +63,18 @@ pub struct Bot {
     pub order_fee: f64,
     pub pnl: f64,
     pub roe: f64,
+
+    // exchange order ids of a live position, 0 when there is none
+    #[serde(default)]
+    pub entry_order_id: i64,
+    #[serde(default)]
+    pub stop_order_id: i64,
+    #[serde(default)]
+    pub take_profit_order_id: i64,
+
+    /// Set under the lock by `DELETE`, the managers leave the bot alone and it's never saved again.
+    #[serde(skip)]
+    pub deleted: bool,
 }
 impl Bot {
     pub fn new(
@@ -76,21 +94,30 @@ impl Bot {
             format_symbol(&symbol)
         );
 
-        let now = tools::get_date(3);
-        let strategy = strategy::get_strategy(&strategy_name);
+        let now = tools::get_date(config::get().time_zone);
+        // names come from the validated config or API, an unknown one leaves the bot paused
+        let (strategy, log) = match strategy::find_strategy(&strategy_name, &Value::Null) {
+            Ok(strategy) => (Some(strategy), String::new()),
+            Err(e) => {
+                error!("bot {}: {}", name, e);
+                (None, format!("paused: {}", e))
+            }
+        };
 
         Self {
             name,
             symbol,
-            is_not_active: false,
+            is_not_active: strategy.is_none(),
+            mode: BotMode::Paper,
             timeframe,
             strategy_name,
+            strategy_params: strategy.as_ref().map(|s| s.params()).unwrap_or_default(),
             group: format!("{:?}{:?}", timeframe, symbol),
             capital,
             last_scanned: now,
             started_at: now,
-            log: "".to_string(),
-            strategy: Some(strategy),
+            log,
+            strategy,
 
             wins: 0,
             losses: 0,
@@ -114,12 +141,16 @@ impl Bot {
             order_fee: 0.0,
             pnl: 0.0,
             roe: 0.0,
+            entry_order_id: 0,
+            stop_order_id: 0,
+            take_profit_order_id: 0,
+            deleted: false,
         }
     }
 
     pub fn reset(&mut self) {
-        self.started_at = tools::get_date(3);
-        self.capital = 100.0;
+        self.started_at = tools::get_date(config::get().time_zone);
+        self.capital = config::get().risk.capital;
         self.is_not_active = false;
         self.in_pos = false;
         self.losses = 0;
@@ -135,14 +166,16 @@ impl Bot {
     #[cfg(debug_assertions)]
     pub fn new_dummy() -> Self {
         let name = format!("{}", "Dummy");
-        let now = tools::get_date(3);
+        let now = tools::get_date(config::get().time_zone);
 
         Self {
             name,
-            symbol: SolUsdt,
+            symbol: Symbol::new("SOLUSDT"),
             is_not_active: false,
+            mode: BotMode::Paper,
             timeframe: Min1,
             strategy_name: "Macd".to_string(),
+            strategy_params: Value::Null,
             capital: 100.0,
             last_scanned: now,
             started_at: now,
@@ -172,6 +205,10 @@ impl Bot {
             order_fee: 0.0,
             pnl: 0.0,
             roe: 0.0,
+            entry_order_id: 0,
+            stop_order_id: 0,
+            take_profit_order_id: 0,
+            deleted: false,
         }
     }
 
@@ -190,38 +227,80 @@ impl Bot {
             );
             return Err("bot can't open position, bot is already in open position".into());
         }
-        if self.capital <= 85.0 {
+        let min_capital = config::get().risk.min_capital_to_stop;
+        if self.capital <= min_capital {
             debug!(
-                "bot can't open position, capital <= 85.0, name: {}",
+                "bot can't open position, capital <= {}, name: {}",
+                min_capital,
                 self.name
             );
-            return Err("bot can't open position, capital <= 85.0, name".into());
+            return Err(format!("bot can't open position, capital <= {}", min_capital).into());
         }
 
         Ok(())
     }
 
-    pub async fn open_position(&mut self, command: &OrderCommand, connector: &BinanceConnector) -> Result<(), Box<dyn Error + Send + Sync>> {
+    pub async fn open_position(&mut self, command: &OrderCommand, connector: &dyn MarketDataSource, now: DateTime<FixedOffset>) -> Result<(), Box<dyn Error + Send + Sync>> {
         self.can_open_position()?;
 
         let price = connector.get_price(&self.symbol).await?;
 
-        self.order_type = *command;
-        self.order_stop_loss = calculate_stop_loss(price, self.stop_loss_ratio, &self.order_type);
-        self.order_take_profit =
-          calculate_take_profit(price, self.take_profit_ratio, &self.order_type);
+        self.open_position_at(command, price, now)
+    }
+
+    /// Opens a position at an already known price, used directly by the backtester.
+    ///
+    /// With known exchange rules for the symbol the quantity is rounded down to the lot size, the
+    /// stop-loss and take-profit to the tick size, and orders below min notional are refused.
+    pub fn open_position_at(&mut self, command: &OrderCommand, price: f64, now: DateTime<FixedOffset>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.can_open_position()?;
 
         let mut capital = self.capital;
-        self.capital -= capital;
+        let mut fee = calculate_taker_fee(capital);
+        capital -= fee;
+        let mut quantity = calculate_buy_quantity(price, self.leverage * capital);
+
+        if let Some(info) = symbols::get(&self.symbol) {
+            quantity = info.round_quantity(quantity);
+            info.check_notional(price, quantity)?;
+
+            // only the margin of the rounded quantity leaves the bot
+            capital = quantity * price / self.leverage;
+            fee = calculate_taker_fee(capital);
+        }
+
+        self.book_position(command, price, quantity, capital, fee, now);
+        Ok(())
+    }
+
+    /// Books a position the exchange already filled, margin and fee follow the filled quantity
+    /// and average price, not what was asked for.
+    pub fn open_filled_position(&mut self, command: &OrderCommand, avg_price: f64, quantity: f64, now: DateTime<FixedOffset>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.can_open_position()?;
 
+        let capital = quantity * avg_price / self.leverage;
         let fee = calculate_taker_fee(capital);
-        capital -= fee;
+        self.book_position(command, avg_price, quantity, capital, fee, now);
+        Ok(())
+    }
 
-        let now = tools::get_date(3);
+    // the margin and the fee leave the bot's capital
+    fn book_position(&mut self, command: &OrderCommand, price: f64, quantity: f64, capital: f64, fee: f64, now: DateTime<FixedOffset>) {
+        let mut stop_loss = calculate_stop_loss(price, self.stop_loss_ratio, command);
+        let mut take_profit = calculate_take_profit(price, self.take_profit_ratio, command);
+        if let Some(info) = symbols::get(&self.symbol) {
+            stop_loss = info.round_price(stop_loss);
+            take_profit = info.round_price(take_profit);
+        }
+
+        self.order_type = *command;
+        self.order_stop_loss = stop_loss;
+        self.order_take_profit = take_profit;
+        self.capital -= capital + fee;
 
         self.order_capital_with_leverage = self.leverage * capital;
         self.order_capital = capital;
-        self.order_quantity = calculate_buy_quantity(price, self.order_capital_with_leverage);
+        self.order_quantity = quantity;
         self.order_entry_price = price;
         self.order_created_at = now;
         self.order_scanned_at = now;
@@ -238,10 +317,9 @@ impl Bot {
         );
 
         self.in_pos = true;
-        Ok(())
     }
 
-    pub fn close_position(&mut self, cur_price: f64) -> Result<Order, Box<dyn Error>> {
+    pub fn close_position(&mut self, cur_price: f64, now: DateTime<FixedOffset>) -> Result<Order, Box<dyn Error + Send + Sync>> {
         if self.order_type == OrderCommand::Wait {
             return Err("No open position to close".into());
         }
@@ -262,9 +340,6 @@ impl Bot {
             &self.order_type,
         );
 
-        let offset = FixedOffset::east_opt(3 * 60 * 60).unwrap(); // +3 utc
-        let now = Utc::now().with_timezone(&offset);
-
         self.update_statistics(pnl);
 
         self.order_fee += fee;
@@ -284,6 +359,8 @@ impl Bot {
             closed_at: now,
             fee: self.order_fee,
             leverage: self.leverage,
+            entry_order_id: self.entry_order_id,
+            exit_order_id: 0,
         };
 
         // reset position state
@@ -300,8 +377,11 @@ impl Bot {
         self.order_scanned_at = now;
         self.pnl = 0.0;
         self.roe = 0.0;
+        self.entry_order_id = 0;
+        self.stop_order_id = 0;
+        self.take_profit_order_id = 0;
 
-        if self.capital <= MIN_CAPITAL_TO_STOP {
+        if self.capital <= config::get().risk.min_capital_to_stop {
             self.is_not_active = true;
         }
 
@@ -317,6 +397,14 @@ impl Bot {
         }
     }
 
+    /// Swaps in a strategy built from `params`, the bot is left untouched when they're invalid.
+    pub fn set_strategy_params(&mut self, params: &Value) -> Result<(), String> {
+        let strategy = strategy::find_strategy(&self.strategy_name, params)?;
+        self.strategy_params = strategy.params();
+        self.strategy = Some(strategy);
+        Ok(())
+    }
+
     pub fn run_strategy(&self, sc: &StrategyContainer) -> (OrderCommand, String) {
         if let Some(s) = &self.strategy {
             return s.run(sc, &self.timeframe, &self.symbol);
@@ -326,13 +414,18 @@ impl Bot {
     }
 
     pub fn is_not_allowed_for_scanning(&self, now: &DateTime<FixedOffset>) -> bool {
-        self.is_not_active || self.capital < MIN_CAPITAL_TO_STOP || !is_timeframe_now(self, now) || self.in_pos
+        self.deleted || self.is_not_active || self.capital < config::get().risk.min_capital_to_stop || !is_timeframe_now(self, now) || self.in_pos
     }
 }
 
+/// `SOLUSDT` -> `Sol`, the spelling bot names have always used.
 fn format_symbol(symbol: &Symbol) -> String {
-    let s = format!("{:?}", symbol);
-    s.strip_suffix("Usdt").unwrap_or(&s).to_string()
+    let base = symbol.base_asset().to_lowercase();
+    let mut chars = base.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => base,
+    }
 }
 
 impl Debug for Bot {
@@ -351,10 +444,12 @@ impl Clone for Bot {
             symbol: self.symbol.clone(),
             timeframe: self.timeframe.clone(),
             strategy_name: self.strategy_name.clone(),
-            strategy: None,
+            strategy_params: self.strategy_params.clone(),
+            strategy: self.strategy.clone(),
             capital: self.capital,
             group: self.group.clone(),
             is_not_active: self.is_not_active,
+            mode: self.mode,
             wins: self.wins,
             losses: self.losses,
             log: self.log.clone(),
@@ -378,6 +473,39 @@ impl Clone for Bot {
             order_fee: self.order_fee,
             pnl: self.pnl,
             roe: self.roe,
+            entry_order_id: self.entry_order_id,
+            stop_order_id: self.stop_order_id,
+            take_profit_order_id: self.take_profit_order_id,
+            deleted: self.deleted,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::SymbolInfo;
+
+    #[test]
+    fn open_position_respects_exchange_filters() {
+        let symbol = Symbol::new("FLTUSDT");
+        symbols::register(SymbolInfo { symbol, tick_size: 0.01, step_size: 0.1, min_notional: 5.0 });
+        let now = tools::get_date(3);
+
+        let mut bot = Bot::new(Timeframe::Min1, symbol, "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.open_position_at(&OrderCommand::Long, 33.333, now).unwrap();
+        assert_eq!(bot.order_quantity, 29.9);
+        assert_eq!(bot.order_stop_loss, 33.2);
+        assert_eq!(bot.order_take_profit, 33.6);
+        // the margin of the rounded-off 0.09 stays in the bot
+        assert!(bot.capital > 0.0);
+        assert!((bot.capital + bot.order_capital + bot.order_fee - 100.0).abs() < 1e-9);
+
+        let mut small = Bot::new(Timeframe::Min1, symbol, "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        small.capital = 90.0;
+        small.leverage = 1.0;
+        assert!(small.open_position_at(&OrderCommand::Long, 1000.0, now).is_err());
+        assert!(!small.in_pos);
+        assert_eq!(small.capital, 90.0);
+    }
+}
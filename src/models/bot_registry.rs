@@ -0,0 +1,183 @@
+use crate::models::bot::Bot;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// One bot shared between the managers and the API.
+///
+/// Writers take the async lock and may hold it across exchange calls, readers only look at the
+/// snapshot published when the writer lets go, so `get_all_bot` never waits on the network.
+#[derive(Debug)]
+pub struct BotHandle {
+    name: String,
+    state: Mutex<Bot>,
+    snapshot: RwLock<Bot>,
+}
+
+impl BotHandle {
+    pub fn new(bot: Bot) -> Self {
+        Self {
+            name: bot.name.clone(),
+            snapshot: RwLock::new(bot.clone()),
+            state: Mutex::new(bot),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn lock(&self) -> BotGuard<'_> {
+        BotGuard {
+            bot: self.state.lock().await,
+            snapshot: &self.snapshot,
+        }
+    }
+
+    /// The bot as of the last released lock.
+    pub fn snapshot(&self) -> Bot {
+        self.snapshot.read().unwrap().clone()
+    }
+}
+
+/// Exclusive access to a bot, publishes the new state to readers on drop.
+pub struct BotGuard<'a> {
+    bot: MutexGuard<'a, Bot>,
+    snapshot: &'a RwLock<Bot>,
+}
+
+impl Deref for BotGuard<'_> {
+    type Target = Bot;
+
+    fn deref(&self) -> &Bot {
+        &self.bot
+    }
+}
+
+impl DerefMut for BotGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Bot {
+        &mut self.bot
+    }
+}
+
+impl Drop for BotGuard<'_> {
+    fn drop(&mut self) {
+        *self.snapshot.write().unwrap() = self.bot.clone();
+    }
+}
+
+/// Every running bot, replaces the old `SharedVec<Bot>`.
+#[derive(Debug, Default)]
+pub struct BotRegistry {
+    bots: RwLock<Vec<Arc<BotHandle>>>,
+}
+
+impl BotRegistry {
+    pub fn new(bots: Vec<Bot>) -> Self {
+        Self {
+            bots: RwLock::new(bots.into_iter().map(|b| Arc::new(BotHandle::new(b))).collect()),
+        }
+    }
+
+    /// The handles at this moment, the list lock isn't held while the caller works on them.
+    pub fn handles(&self) -> Vec<Arc<BotHandle>> {
+        self.bots.read().unwrap().clone()
+    }
+
+    pub fn find(&self, name: &str) -> Option<Arc<BotHandle>> {
+        self.bots.read().unwrap().iter().find(|b| b.name() == name).cloned()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Bot> {
+        self.find(name).map(|b| b.snapshot())
+    }
+
+    pub fn snapshot(&self) -> Vec<Bot> {
+        self.bots.read().unwrap().iter().map(|b| b.snapshot()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bots.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a bot, `false` when the name is already taken.
+    pub fn insert(&self, bot: Bot) -> bool {
+        let mut bots = self.bots.write().unwrap();
+        if bots.iter().any(|b| b.name() == bot.name) {
+            return false;
+        }
+        bots.push(Arc::new(BotHandle::new(bot)));
+        true
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Arc<BotHandle>> {
+        let mut bots = self.bots.write().unwrap();
+        let index = bots.iter().position(|b| b.name() == name)?;
+        Some(bots.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{Symbol, Timeframe};
+
+    fn bot(symbol: Symbol) -> Bot {
+        Bot::new(Timeframe::Min1, symbol, "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1)
+    }
+
+    #[tokio::test]
+    async fn readers_see_state_after_the_writer_releases() {
+        let registry = BotRegistry::new(vec![bot(Symbol::new("SOLUSDT"))]);
+        let handle = registry.handles().remove(0);
+
+        let mut guard = handle.lock().await;
+        guard.capital = 50.0;
+        assert_eq!(registry.snapshot()[0].capital, 100.0);
+        drop(guard);
+
+        assert_eq!(registry.get(handle.name()).unwrap().capital, 50.0);
+    }
+
+    #[tokio::test]
+    async fn concurrent_writers_do_not_lose_updates() {
+        let registry = Arc::new(BotRegistry::new(vec![bot(Symbol::new("SOLUSDT"))]));
+        let name = registry.snapshot()[0].name.clone();
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let registry = Arc::clone(&registry);
+                let name = name.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        let handle = registry.find(&name).unwrap();
+                        let mut bot = handle.lock().await;
+                        bot.wins += 1;
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(registry.get(&name).unwrap().wins, 800);
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let registry = BotRegistry::new(vec![bot(Symbol::new("SOLUSDT"))]);
+        assert!(!registry.insert(bot(Symbol::new("SOLUSDT"))));
+        assert!(registry.insert(bot(Symbol::new("ETHUSDT"))));
+        assert_eq!(registry.len(), 2);
+
+        let name = registry.snapshot()[1].name.clone();
+        assert!(registry.remove(&name).is_some());
+        assert!(registry.get(&name).is_none());
+    }
+}
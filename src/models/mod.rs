@@ -1,2 +1,3 @@
 pub mod bot;
+pub mod bot_registry;
 pub mod models;
@@ -1,10 +1,14 @@
-use crate::enums::{OrderCommand, Symbol, Timeframe};
+use crate::enums::{BotMode, OrderCommand, Symbol, Timeframe};
+use crate::executor::BinanceExecutor;
+use crate::indicators::{Indicator, IndicatorSpec, IndicatorStream};
+use crate::market_stream::MarketStream;
 use crate::repository::Repository;
-use crate::{ta, tools};
-use chrono::{DateTime, FixedOffset, Utc};
+use crate::{config, tools};
+use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
-use std::cell::UnsafeCell;
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use crate::strategy::str_impl::StocBorder;
 
 #[derive(Debug, Clone)]
@@ -15,7 +19,30 @@ pub struct Candle {
     pub high: f64,
     pub low: f64,
     pub open_time: u64,
+    /// Last millisecond of the bar, `open_time + duration - 1` like Binance reports it.
+    pub close_time: u64,
     pub volume: f64,
+    /// `false` while the bar is still forming, its prices can still change.
+    pub is_closed: bool,
+}
+
+/// Extremes of the traded price over a window, `last` is the price at the end of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceRange {
+    pub high: f64,
+    pub low: f64,
+    pub last: f64,
+}
+impl PriceRange {
+    pub fn from_price(price: f64) -> Self {
+        Self { high: price, low: price, last: price }
+    }
+
+    pub fn update(&mut self, price: f64) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.last = price;
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,15 +59,19 @@ pub struct Order {
     pub closed_at: DateTime<FixedOffset>,
     pub fee: f64,
     pub leverage: f64,
+    // exchange order ids for live bots, 0 for paper trades
+    #[serde(default)]
+    pub entry_order_id: i64,
+    #[serde(default)]
+    pub exit_order_id: i64,
 }
 impl Order {
     #[allow(dead_code)]
     #[cfg(debug_assertions)]
     pub fn dummy() -> Self {
-        let offset = FixedOffset::east_opt(3 * 60 * 60).unwrap(); // +3 utc
-        let now = Utc::now().with_timezone(&offset);
+        let now = tools::get_date(config::get().time_zone);
         Self {
-            symbol: Symbol::SolUsdt,
+            symbol: Symbol::new("SOLUSDT"),
             order_type: OrderCommand::Long,
             bot_name: "dummy".to_string(),
             entry_price: 100.0,
@@ -52,6 +83,8 @@ impl Order {
             closed_at: now,
             fee: 0.1,
             leverage: 10.0,
+            entry_order_id: 0,
+            exit_order_id: 0,
         }
     }
 }
@@ -67,56 +100,134 @@ pub struct SystemInfo {
 #[derive(Debug, Clone)]
 pub struct Container {
     pub repository: Repository,
+    // set when exchange credentials are configured, required by live bots
+    pub executor: Option<Arc<BinanceExecutor>>,
+    // the server's market data, bots created through the API subscribe their pair on it
+    pub market_stream: Option<Arc<MarketStream>>,
 }
 
 
 #[derive(Debug)]
 pub struct StrategyContainer {
-    pub candles_map: HashMap<(Timeframe, Symbol), Vec<Candle>>,
-    pub macd: HashMap<(Timeframe, Symbol), Macd>,
-    pub ema: HashMap<(Timeframe, Symbol, usize), Vec<f64>>,
-    pub stochastic: HashMap<(Timeframe, Symbol), Stochastic>,
+    // closed bars only, indicators are computed from these
+    candles_map: HashMap<(Timeframe, Symbol), Vec<Candle>>,
+    live_bars: HashMap<(Timeframe, Symbol), Candle>,
+    // what the bots' strategies read, kept across bars
+    required: HashMap<(Timeframe, Symbol), HashSet<IndicatorSpec>>,
+    // also kept across bars, each one is fed the new closed candles only
+    streams: HashMap<(Timeframe, Symbol, IndicatorSpec), IndicatorStream>,
+    // streams brought up to date with this bar's candles
+    updated: HashSet<(Timeframe, Symbol, IndicatorSpec)>,
 }
 impl StrategyContainer {
     pub fn new() -> Self {
         Self {
             candles_map: HashMap::new(),
-            macd: HashMap::new(),
-            ema: HashMap::new(),
-            stochastic: HashMap::new(),
+            live_bars: HashMap::new(),
+            required: HashMap::new(),
+            streams: HashMap::new(),
+            updated: HashSet::new(),
         }
     }
+
+    /// Drops the bar's candles, indicators are hidden until the next `calculate_all`.
     pub fn reset(&mut self) {
         self.candles_map.clear();
-        self.macd.clear();
-        self.ema.clear();
+        self.live_bars.clear();
+        self.updated.clear();
+    }
+
+    pub fn require(&mut self, timeframe: Timeframe, symbol: Symbol, specs: &[IndicatorSpec]) {
+        self.required.entry((timeframe, symbol)).or_default().extend(specs.iter().copied());
     }
 
+    pub fn clear_requirements(&mut self) {
+        self.required.clear();
+    }
+
+    /// Updates each required indicator once for every series that has candles.
     pub fn calculate_all(&mut self) {
         for ((timeframe, symbol), candles) in self.candles_map.iter() {
-            let close_prices = tools::get_close_prices(candles);
-            let high_prices = tools::get_high_prices(candles);
-            let low_prices = tools::get_low_prices(candles);
-            let (macd, signal, histogram) = ta::macd_slice(&close_prices);
-            let (k, d) = ta::stochastic_slice(&close_prices, &high_prices, &low_prices, 14, 1, 3);
-            self.macd.insert((*timeframe, *symbol), Macd { macd, signal, histogram });
-            self.ema.insert((*timeframe, *symbol, 20), ta::ema_slice(&close_prices, 20));
-            self.ema.insert((*timeframe, *symbol, 50), ta::ema_slice(&close_prices, 50));
-            self.ema.insert((*timeframe, *symbol, 200), ta::ema_slice(&close_prices, 200));
-            self.stochastic.insert((*timeframe, *symbol), Stochastic {k, d});
+            let Some(specs) = self.required.get(&(*timeframe, *symbol)) else {
+                continue;
+            };
+            for spec in specs {
+                let key = (*timeframe, *symbol, *spec);
+                self.streams.entry(key).or_insert_with(|| IndicatorStream::new(*spec)).update(candles);
+                self.updated.insert(key);
+            }
         }
+
+        let required = &self.required;
+        self.streams.retain(|(timeframe, symbol, spec), _| required.get(&(*timeframe, *symbol)).is_some_and(|s| s.contains(spec)));
     }
 
-    pub fn get_macd(&self, timeframe: &Timeframe, symbol: &Symbol) -> Option<&Macd> {
-        self.macd.get(&(*timeframe, *symbol))
+    /// Stores a kline series, a trailing forming bar is kept apart from the closed ones.
+    pub fn insert_candles(&mut self, timeframe: Timeframe, symbol: Symbol, mut candles: Vec<Candle>) {
+        if candles.last().is_some_and(|c| !c.is_closed) {
+            let live = candles.pop().unwrap();
+            self.live_bars.insert((timeframe, symbol), live);
+        }
+        candles.retain(|c| c.is_closed);
+        self.candles_map.insert((timeframe, symbol), candles);
+    }
+
+    /// Closed bars, the last one is final and won't repaint.
+    pub fn get_candles(&self, timeframe: &Timeframe, symbol: &Symbol) -> Option<&Vec<Candle>> {
+        self.candles_map.get(&(*timeframe, *symbol))
+    }
+
+    /// The bar still forming, for strategies that explicitly want intrabar prices.
+    pub fn get_live_bar(&self, timeframe: &Timeframe, symbol: &Symbol) -> Option<&Candle> {
+        self.live_bars.get(&(*timeframe, *symbol))
+    }
+
+    pub fn get(&self, timeframe: &Timeframe, symbol: &Symbol, spec: &IndicatorSpec) -> Option<&Indicator> {
+        let key = (*timeframe, *symbol, *spec);
+        if !self.updated.contains(&key) {
+            return None;
+        }
+        self.streams.get(&key).map(|s| s.output())
+    }
+
+    pub fn get_macd(&self, timeframe: &Timeframe, symbol: &Symbol, fast: usize, slow: usize, signal: usize) -> Option<&Macd> {
+        match self.get(timeframe, symbol, &IndicatorSpec::Macd { fast, slow, signal }) {
+            Some(Indicator::Macd(macd)) => Some(macd),
+            _ => None,
+        }
     }
 
     pub fn get_ema(&self, timeframe: &Timeframe, symbol: &Symbol, period: usize) -> Option<&Vec<f64>> {
-        self.ema.get(&(*timeframe, *symbol, period))
+        match self.get(timeframe, symbol, &IndicatorSpec::Ema { period }) {
+            Some(Indicator::Line(ema)) => Some(ema),
+            _ => None,
+        }
+    }
+
+    pub fn get_stochastic(&self, timeframe: &Timeframe, symbol: &Symbol, period_k: usize, smooth_k: usize, period_d: usize) -> Option<&Stochastic> {
+        match self.get(timeframe, symbol, &IndicatorSpec::Stochastic { period_k, smooth_k, period_d }) {
+            Some(Indicator::Stochastic(stochastic)) => Some(stochastic),
+            _ => None,
+        }
     }
-    
-    pub fn get_stochastic(&self, timeframe: &Timeframe, symbol: &Symbol) -> Option<&Stochastic> {
-        self.stochastic.get(&(*timeframe, *symbol))
+
+    /// Single series indicators: EMA, RSI, ATR, VWAP, OBV, PSAR, CCI and Williams %R.
+    pub fn get_line(&self, timeframe: &Timeframe, symbol: &Symbol, spec: &IndicatorSpec) -> Option<&Vec<f64>> {
+        match self.get(timeframe, symbol, spec) {
+            Some(Indicator::Line(line)) => Some(line),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn indicator_count(&self) -> usize {
+        self.updated.len()
+    }
+}
+
+impl Default for StrategyContainer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -159,13 +270,84 @@ pub struct Stochastic {
     pub d: Vec<f64>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Dmi {
+    pub adx: Vec<f64>,
+    pub plus_di: Vec<f64>,
+    pub minus_di: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SuperTrend {
+    pub line: Vec<f64>,
+    /// 1 in an up trend, -1 in a down trend.
+    pub direction: Vec<f64>,
+}
+
+/// Keltner or Donchian channel.
+#[derive(Debug, Clone)]
+pub struct Bands {
+    pub upper: Vec<f64>,
+    pub middle: Vec<f64>,
+    pub lower: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ichimoku {
+    pub conversion: Vec<f64>,
+    pub base: Vec<f64>,
+    pub span_a: Vec<f64>,
+    pub span_b: Vec<f64>,
+    pub lagging: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeikinAshi {
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+}
+
 #[derive(Deserialize)]
 pub struct TimeRange {
     pub(crate) start_time: String,
     pub(crate) end_time: String,
 }
 
-pub struct SharedVec<T>(pub UnsafeCell<Vec<T>>);
+#[derive(Debug, Deserialize)]
+pub struct NewBot {
+    pub symbol: Symbol,
+    pub timeframe: Timeframe,
+    pub strategy_name: String,
+    pub capital: f64,
+    pub leverage: f64,
+    pub take_profit_ratio: f64,
+    pub stop_loss_ratio: f64,
+    pub trailing_stop_activation_point: f64,
+    #[serde(default = "default_trailing_stop")]
+    pub is_trailing_stop_active: bool,
+    #[serde(default)]
+    pub mode: BotMode,
+    /// Defaults to `{strategy}_{timeframe}_{symbol}`, needed to run a strategy twice on one pair.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub strategy_params: Value,
+}
 
-unsafe impl<T> Send for SharedVec<T> {}
-unsafe impl<T> Sync for SharedVec<T> {}
\ No newline at end of file
+fn default_trailing_stop() -> bool {
+    true
+}
+
+/// Fields a `PATCH` may change, the rest of the bot is left alone.
+#[derive(Debug, Deserialize, Default)]
+pub struct BotUpdate {
+    pub capital: Option<f64>,
+    pub leverage: Option<f64>,
+    pub take_profit_ratio: Option<f64>,
+    pub stop_loss_ratio: Option<f64>,
+    pub is_trailing_stop_active: Option<bool>,
+    pub trailing_stop_activation_point: Option<f64>,
+    pub strategy_params: Option<Value>,
+}
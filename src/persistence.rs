@@ -0,0 +1,68 @@
+use crate::config;
+use crate::models::bot_registry::BotRegistry;
+use crate::models::models::Container;
+use log::{debug, error};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+/// Saves every bot each `database.snapshot_interval_secs` until `token` is cancelled, a crash
+/// loses at most one interval of state.
+pub async fn run_snapshots(bots: Arc<BotRegistry>, c: Arc<Container>, token: CancellationToken) {
+    let period = Duration::from_secs(config::get().database.snapshot_interval_secs);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        match save_snapshot(&bots, &c).await {
+            Ok(count) => debug!("saved state of {} bots", count),
+            Err(e) => error!("Failed to save bot state: {}", e),
+        }
+    }
+}
+
+/// Writes the last published state of every bot in one transaction, returns how many were saved.
+pub async fn save_snapshot(bots: &BotRegistry, c: &Container) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let snapshot = bots.snapshot();
+    let count = snapshot.len();
+    c.repository.save_bot_state(snapshot).await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{OrderCommand, Symbol, Timeframe};
+    use crate::models::bot::Bot;
+    use crate::repository::Repository;
+    use crate::tools;
+
+    #[tokio::test]
+    async fn open_positions_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("traderrs_persistence_{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let now = tools::get_date(0);
+
+        let mut bot = Bot::new(Timeframe::Min5, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.open_position_at(&OrderCommand::Long, 150.0, now).unwrap();
+        let opened = bot.clone();
+        let bots = BotRegistry::new(vec![bot]);
+        let c = Container { repository: Repository::new(path.clone()).unwrap(), executor: None, market_stream: None };
+        assert_eq!(save_snapshot(&bots, &c).await.unwrap(), 1);
+        drop(c);
+
+        let restored = Repository::new(path).unwrap().get_bot_state().await.unwrap();
+        let bot = &restored[0];
+        assert!(bot.in_pos);
+        assert_eq!(bot.order_type, opened.order_type);
+        assert_eq!(bot.order_created_at, opened.order_created_at);
+        assert_eq!((bot.order_entry_price, bot.order_quantity, bot.order_stop_loss), (opened.order_entry_price, opened.order_quantity, opened.order_stop_loss));
+        assert_eq!(bot.capital, opened.capital);
+    }
+}
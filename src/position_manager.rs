@@ -1,78 +1,109 @@
-use crate::connector::BinanceConnector;
-use crate::enums::Symbol;
+use crate::connector::MarketDataSource;
+use crate::enums::{BotMode, Symbol};
 use crate::models::bot::Bot;
-use crate::models::models::{Container, Order, SharedVec};
+use crate::models::bot_registry::{BotHandle, BotRegistry};
+use crate::models::models::{Container, Order, PriceRange};
 use crate::tools;
-use crate::tools::{shift_stop_loss, should_close_position, update_pnl_and_roe};
+use crate::tools::{get_exit_price, shift_stop_loss, update_pnl_and_roe};
 use chrono::{DateTime, FixedOffset};
 use log::{debug, error, warn};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
-use crate::constants::MIN_CAPITAL_TO_STOP;
+use tokio_util::sync::CancellationToken;
+use crate::config;
 
 pub struct PositionManager {
-    bots: Arc<SharedVec<Bot>>,
-    connector: Arc<BinanceConnector>,
+    bots: Arc<BotRegistry>,
+    connector: Arc<dyn MarketDataSource>,
     container: Arc<Container>,
+    last_tick: Option<DateTime<FixedOffset>>,
+    // closes the database didn't take yet, retried every tick
+    unsaved: Vec<Order>,
 }
 
 impl PositionManager {
     pub fn new(
-        bots: Arc<SharedVec<Bot>>,
-        connector: Arc<BinanceConnector>,
+        bots: Arc<BotRegistry>,
+        connector: Arc<dyn MarketDataSource>,
         container: Arc<Container>,
     ) -> Self {
         Self {
             bots,
             connector,
             container,
+            last_tick: None,
+            unsaved: Vec::new(),
         }
     }
 
-    pub async fn start(&mut self) {
+    /// Ticks until `token` is cancelled, a tick in progress is always finished.
+    pub async fn start(&mut self, token: CancellationToken) {
         debug!("Starting Position Manager...");
         let sleep_time = 1500;
-        let mut prices: HashMap<Symbol, f64> = HashMap::with_capacity(20);
-        let mut to_close: Vec<Order> = Vec::with_capacity(prices.len());
         let mut now: DateTime<FixedOffset>;
 
-        let mut fetch_tasks: Vec<JoinHandle<Option<(Symbol, f64)>>> = Vec::new();
-        let mut fetch_symbols: HashMap<Symbol, ()> = HashMap::new();
-        let bots: &mut Vec<Bot>;
+        loop {
+            now = tools::get_date(config::get().time_zone);
 
-        unsafe {
-            bots = &mut *self.bots.0.get();
+            self.tick(now).await;
+
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = tokio::time::sleep(std::time::Duration::from_millis(sleep_time)) => {}
+            }
         }
+        // last try for closes the database refused, the process exits after this
+        self.handle_closed_position(&mut Vec::new()).await;
+        debug!("Position Manager stopped");
+    }
 
-        loop {
-            now = tools::get_date(3);
+    /// Checks every open position once at `now`.
+    pub async fn tick(&mut self, now: DateTime<FixedOffset>) {
+        let mut prices: HashMap<Symbol, PriceRange> = HashMap::with_capacity(20);
+        let mut to_close: Vec<Order> = Vec::new();
+        let mut fetch_tasks: Vec<JoinHandle<Option<(Symbol, PriceRange)>>> = Vec::new();
+        let mut fetch_symbols: HashMap<Symbol, ()> = HashMap::new();
+        let bots = self.bots.handles();
 
-            self.update_prices(bots, &mut prices, &mut fetch_tasks, &mut fetch_symbols).await;
+        self.update_prices(&bots, &mut prices, &mut fetch_tasks, &mut fetch_symbols).await;
 
-            self.scan_bots(bots, &prices, &mut to_close, now).await;
+        self.scan_bots(&bots, &prices, &mut to_close, now).await;
 
-            self.handle_closed_position(&mut to_close).await;
+        self.handle_closed_position(&mut to_close).await;
 
-            tokio::time::sleep(std::time::Duration::from_millis(sleep_time)).await;
-        }
+        self.last_tick = Some(now);
     }
 
-    async fn scan_bots(&mut self, bots: &mut Vec<Bot>, prices: &HashMap<Symbol, f64>, to_close: &mut Vec<Order>, now: DateTime<FixedOffset>) {
+    async fn scan_bots(&mut self, bots: &[Arc<BotHandle>], prices: &HashMap<Symbol, PriceRange>, to_close: &mut Vec<Order>, now: DateTime<FixedOffset>) {
         if prices.is_empty() {
             return;
         }
 
-        for bot in bots.iter_mut() {
-            if !bot.in_pos { continue; }
+        for handle in bots.iter() {
+            let mut guard = handle.lock().await;
+            let bot = &mut *guard;
+            if !bot.in_pos || bot.deleted { continue; }
 
-            if let Some(&price) = prices.get(&bot.symbol) {
-                if should_close_position(price, &bot) {
-                    if let Ok(order) = bot.close_position(price) {
+            if bot.mode == BotMode::Live {
+                self.scan_live_bot(bot, prices, to_close, now).await;
+                continue;
+            }
+
+            if let Some(&range) = prices.get(&bot.symbol) {
+                // extremes from before the entry must not trigger the new levels
+                let range = match self.last_tick {
+                    Some(last_tick) if bot.order_created_at > last_tick => PriceRange::from_price(range.last),
+                    _ => range,
+                };
+                let price = range.last;
+
+                if let Some(exit_price) = get_exit_price(&range, bot, config::get().risk.exit_slippage) {
+                    if let Ok(order) = bot.close_position(exit_price, now) {
                         to_close.push(order);
-                        if bot.capital <= MIN_CAPITAL_TO_STOP {
-                            if let Err(e) = self.container.repository.create_bot(bot) {
+                        if bot.capital <= config::get().risk.min_capital_to_stop {
+                            if let Err(e) = self.container.repository.create_bot(bot).await {
                                 bot.log = e.to_string();
                             }
                             bot.reset();
@@ -91,21 +122,79 @@ impl PositionManager {
         }
     }
 
+    /// Exits of live bots rest on the exchange, here they are only reconciled and trailed.
+    async fn scan_live_bot(&self, bot: &mut Bot, prices: &HashMap<Symbol, PriceRange>, to_close: &mut Vec<Order>, now: DateTime<FixedOffset>) {
+        let Some(executor) = self.container.executor.as_ref() else {
+            bot.log = "live bot without exchange credentials".to_string();
+            return;
+        };
+
+        match executor.sync_position(bot, now).await {
+            Ok(Some(order)) => {
+                to_close.push(order);
+                if bot.capital <= config::get().risk.min_capital_to_stop {
+                    if let Err(e) = self.container.repository.create_bot(bot).await {
+                        bot.log = e.to_string();
+                    }
+                    bot.reset();
+                }
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to sync live position for {}: {}", bot.name, e);
+                bot.log = e.to_string();
+            }
+        }
+
+        let Some(range) = prices.get(&bot.symbol) else {
+            bot.log = "price is missing".to_string();
+            return;
+        };
+
+        update_pnl_and_roe(bot, range.last);
+        let stop_loss = bot.order_stop_loss;
+        shift_stop_loss(bot);
+        if bot.order_stop_loss != stop_loss {
+            if let Err(e) = executor.replace_stop_loss(bot).await {
+                error!("Failed to move stop-loss for {}: {}", bot.name, e);
+                bot.log = e.to_string();
+            }
+        }
+        bot.last_scanned = now;
+    }
+
+    /// Saves the orders with the current state of the bots that closed them, one transaction so a
+    /// restart can't close twice. On failure the orders are kept and retried on the next tick.
     async fn handle_closed_position(&mut self, orders: &mut Vec<Order>) {
-        if orders.is_empty() {
+        self.unsaved.append(orders);
+        if self.unsaved.is_empty() {
             return;
         }
 
-        self.container.repository.create_orders(&orders).unwrap();
-        orders.clear();
+        let mut bots: Vec<Bot> = Vec::new();
+        for order in self.unsaved.iter() {
+            if bots.iter().any(|b| b.name == order.bot_name) {
+                continue;
+            }
+            if let Some(bot) = self.bots.get(&order.bot_name) {
+                bots.push(bot);
+            }
+        }
+
+        match self.container.repository.save_closed_positions(self.unsaved.clone(), bots).await {
+            Ok(()) => self.unsaved.clear(),
+            Err(e) => error!("Failed to save {} closed positions, retrying next tick: {}", self.unsaved.len(), e),
+        }
     }
 
-    async fn update_prices(&self, bots: &Vec<Bot>, prices: &mut HashMap<Symbol, f64>, fetch_tasks: &mut Vec<JoinHandle<Option<(Symbol, f64)>>>, fetch_symbols: &mut HashMap<Symbol, ()>) {
+    async fn update_prices(&self, bots: &[Arc<BotHandle>], prices: &mut HashMap<Symbol, PriceRange>, fetch_tasks: &mut Vec<JoinHandle<Option<(Symbol, PriceRange)>>>, fetch_symbols: &mut HashMap<Symbol, ()>) {
         fetch_tasks.clear();
         fetch_symbols.clear();
         prices.clear();
 
-        for bot in bots.iter() {
+        for handle in bots.iter() {
+            let bot = handle.snapshot();
             if !bot.in_pos {
                 continue;
             }
@@ -119,9 +208,9 @@ impl PositionManager {
             let connector = Arc::clone(&self.connector);
             let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
 
-            let handler: JoinHandle<Option<(Symbol, f64)>> = tokio::spawn(async move {
+            let handler: JoinHandle<Option<(Symbol, PriceRange)>> = tokio::spawn(async move {
                 let _permit = permit;
-                match connector.get_price(&smb).await {
+                match connector.take_price_range(&smb).await {
                     Ok(price) => Some((smb, price)),
                     Err(e) => {
                         error!("Error fetching price for {:?}: {}", smb, e);
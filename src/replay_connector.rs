@@ -0,0 +1,258 @@
+use crate::config;
+use crate::connector::{parse_klines, MarketDataSource};
+use crate::entry_manager::EntryManager;
+use crate::enums::{Symbol, Timeframe};
+use crate::models::models::{Candle, PriceRange};
+use crate::position_manager::PositionManager;
+use crate::tools;
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use log::debug;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Serves historical klines from files instead of the exchange.
+///
+/// Only candles that are fully closed at the simulated clock are visible, so a replay
+/// never sees prices from the future.
+#[derive(Debug)]
+pub struct ReplayConnector {
+    candles: HashMap<(Timeframe, Symbol), Vec<Candle>>,
+    now_ms: AtomicU64,
+    // clock of the previous `take_price_range` per symbol
+    ranges_taken_at: Mutex<HashMap<Symbol, u64>>,
+}
+
+impl ReplayConnector {
+    pub fn new(start_ms: u64) -> Self {
+        Self {
+            candles: HashMap::new(),
+            now_ms: AtomicU64::new(start_ms),
+            ranges_taken_at: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Loads klines from a `.csv` or `.json` file, picked by extension.
+    pub fn load_file(&mut self, symbol: Symbol, timeframe: Timeframe, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let candles = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => read_json(path, timeframe)?,
+            _ => read_csv(path, timeframe)?,
+        };
+        self.insert_candles(symbol, timeframe, candles);
+        Ok(())
+    }
+
+    pub fn insert_candles(&mut self, symbol: Symbol, timeframe: Timeframe, mut candles: Vec<Candle>) {
+        candles.sort_by_key(|c| c.open_time);
+        candles.dedup_by_key(|c| c.open_time);
+        debug!("replay loaded {} candles for {:?} {:?}", candles.len(), symbol, timeframe);
+        self.candles.insert((timeframe, symbol), candles);
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn now(&self, time_zone: i32) -> DateTime<FixedOffset> {
+        tools::date_from_millis(self.now_ms(), time_zone)
+    }
+
+    pub fn set_time(&self, ms: u64) {
+        self.now_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    /// First and last open time over every loaded series.
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        let first = self.candles.values().filter_map(|c| c.first()).map(|c| c.open_time).min()?;
+        let last = self.candles.values().filter_map(|c| c.last()).map(|c| c.open_time).max()?;
+        Some((first, last))
+    }
+
+    fn finest_timeframe(&self, symbol: &Symbol) -> Option<Timeframe> {
+        self.candles.keys().filter(|(_, s)| s == symbol).map(|(tf, _)| *tf).min()
+    }
+
+    fn closed_candles(&self, symbol: &Symbol, timeframe: &Timeframe) -> &[Candle] {
+        let Some(candles) = self.candles.get(&(*timeframe, *symbol)) else {
+            return &[];
+        };
+        let now = self.now_ms();
+        let end = candles.partition_point(|c| c.close_time < now);
+        &candles[..end]
+    }
+}
+
+#[async_trait]
+impl MarketDataSource for ReplayConnector {
+    async fn get_price(&self, symbol: &Symbol) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        // the finest loaded timeframe gives the freshest close
+        self.finest_timeframe(symbol)
+            .and_then(|tf| self.closed_candles(symbol, &tf).last())
+            .map(|c| c.close)
+            .ok_or_else(|| format!("no replay price for {:?} at {}", symbol, self.now_ms()).into())
+    }
+
+    async fn get_candles(
+        &self,
+        symbol: Symbol,
+        timeframe: Timeframe,
+        limit: i32,
+    ) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+        let candles = self.closed_candles(&symbol, &timeframe);
+        if candles.is_empty() {
+            return Err(format!("no replay candles for {:?} {:?}", symbol, timeframe).into());
+        }
+        let start = candles.len().saturating_sub(limit.max(0) as usize);
+        Ok(candles[start..].to_vec())
+    }
+
+    async fn take_price_range(&self, symbol: &Symbol) -> Result<PriceRange, Box<dyn Error + Send + Sync>> {
+        let price = self.get_price(symbol).await?;
+        let now = self.now_ms();
+        let since = self.ranges_taken_at.lock().unwrap().insert(*symbol, now);
+
+        let mut range = PriceRange::from_price(price);
+        if let (Some(since), Some(tf)) = (since, self.finest_timeframe(symbol)) {
+            for c in self.closed_candles(symbol, &tf).iter().rev() {
+                if c.open_time < since {
+                    break;
+                }
+                range.high = range.high.max(c.high);
+                range.low = range.low.min(c.low);
+            }
+        }
+
+        Ok(range)
+    }
+}
+
+/// Drives both managers over `[from_ms, to_ms)` on the replay clock.
+///
+/// Positions are checked every `position_step_ms`, entries are scanned on every minute boundary,
+/// the same cadence as the live loops.
+pub async fn run_replay(
+    connector: &ReplayConnector,
+    entry_manager: &mut EntryManager,
+    position_manager: &mut PositionManager,
+    from_ms: u64,
+    to_ms: u64,
+    position_step_ms: u64,
+) {
+    let minute = 60_000;
+    let step = position_step_ms.clamp(1, minute);
+    connector.set_time(from_ms);
+
+    while connector.now_ms() < to_ms {
+        let now = connector.now(config::get().time_zone);
+        position_manager.tick(now).await;
+        if connector.now_ms().is_multiple_of(minute) {
+            entry_manager.tick(&now).await;
+        }
+        connector.advance(step);
+    }
+}
+
+fn read_json(path: &Path, timeframe: Timeframe) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+    let content = fs::read_to_string(path)?;
+    let entries: Vec<Value> = serde_json::from_str(&content)?;
+    parse_klines(&entries, timeframe)
+}
+
+/// Reads Binance kline dumps: `open_time,open,high,low,close,volume,close_time,...`, header line optional.
+fn read_csv(path: &Path, timeframe: Timeframe) -> Result<Vec<Candle>, Box<dyn Error + Send + Sync>> {
+    let content = fs::read_to_string(path)?;
+    let mut candles = Vec::new();
+
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() < 6 {
+            continue;
+        }
+        let Ok(open_time) = fields[0].parse::<u64>() else {
+            // header
+            continue;
+        };
+
+        candles.push(Candle {
+            close: fields[4].parse()?,
+            open: fields[1].parse()?,
+            high: fields[2].parse()?,
+            low: fields[3].parse()?,
+            open_time,
+            close_time: match fields.get(6) {
+                Some(f) => f.parse()?,
+                None => open_time + timeframe.duration_ms() - 1,
+            },
+            volume: fields[5].parse()?,
+            is_closed: true,
+        });
+    }
+
+    Ok(candles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::OrderCommand;
+    use crate::models::bot::Bot;
+    use crate::models::bot_registry::BotRegistry;
+    use crate::models::models::Container;
+    use crate::repository::Repository;
+    use crate::strategy::strategy::find_strategy;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    // 2025-01-06 00:00 UTC, first open time of the fixture
+    const START_MS: u64 = 1_736_121_600_000;
+
+    #[tokio::test]
+    async fn replay_trades_through_both_managers() {
+        // 40 one minute bars falling from 100 to 96, then 40 rising to 104
+        let symbol = Symbol::new("REPLAYUSDT");
+        let mut connector = ReplayConnector::new(START_MS);
+        connector.load_file(symbol, Timeframe::Min1, &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/replay_1m.csv")).unwrap();
+        assert_eq!(connector.time_range(), Some((START_MS, START_MS + 79 * 60_000)));
+        let connector = Arc::new(connector);
+
+        let mut bot = Bot::new(Timeframe::Min1, symbol, "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.is_trailing_stop_active = false;
+        let strategy = find_strategy("EmaMacd", &json!({"trend_ema": 5, "macd_fast": 3, "macd_slow": 6, "macd_signal": 3})).unwrap();
+        bot.strategy_params = strategy.params();
+        bot.strategy = Some(strategy);
+        let name = bot.name.clone();
+
+        let path = std::env::temp_dir().join(format!("traderrs_replay_{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let c = Arc::new(Container { repository: Repository::new(path).unwrap(), executor: None, market_stream: None });
+        let bots = Arc::new(BotRegistry::new(vec![bot]));
+        let mut entry_manager = EntryManager::new(Arc::clone(&bots), connector.clone(), Arc::clone(&c));
+        let mut position_manager = PositionManager::new(Arc::clone(&bots), connector.clone(), Arc::clone(&c));
+
+        run_replay(&connector, &mut entry_manager, &mut position_manager, START_MS + 10 * 60_000, START_MS + 80 * 60_000, 60_000).await;
+
+        // MACD turns positive on the bar closing at 96.60, the take-profit 0.8% above is hit four bars later
+        let orders = c.repository.get_order_by_bot_name(name.clone()).await.unwrap();
+        assert_eq!(orders.len(), 1, "{:?}", orders);
+        let order = &orders[0];
+        let tz = config::get().time_zone;
+        assert_eq!((order.order_type, order.entry_price), (OrderCommand::Long, 96.6));
+        assert_eq!(order.exit_price, 96.6 * 1.008 * (1.0 - config::get().risk.exit_slippage / 100.0));
+        assert_eq!(order.created_at, tools::date_from_millis(START_MS + 43 * 60_000, tz));
+        assert_eq!(order.closed_at, tools::date_from_millis(START_MS + 47 * 60_000, tz));
+        assert!(order.pnl > 0.0);
+
+        let bot = bots.get(&name).unwrap();
+        assert!(!bot.in_pos);
+        assert_eq!((bot.wins, bot.losses), (1, 0));
+    }
+}
@@ -1,394 +1,673 @@
+use crate::config;
 use crate::models::bot::Bot;
 use crate::models::models::{Order, StatisticResult};
 use crate::strategy::strategy;
 use crate::tools;
 use chrono::{DateTime, FixedOffset};
+use log::{error, info};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{params, Connection, Result};
+use serde_json::Value;
+use std::error::Error;
 use std::path::PathBuf;
+use std::time::Duration;
+
+// SQLite allows one writer at a time, a few connections are enough for concurrent readers
+const POOL_SIZE: u32 = 4;
 
 #[derive(Debug, Clone)]
 pub struct Repository {
-    path: PathBuf,
+    pool: Pool<SqliteConnectionManager>,
 }
 impl Repository {
-    pub fn new(db_path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(&db_path)?;
-
-        conn.execute_batch("
-                CREATE TABLE IF NOT EXISTS bot_state (
-                    name TEXT PRIMARY KEY,
-                    symbol TEXT,
-                    timeframe TEXT,
-                    strategy_name TEXT,
-                    capital INTEGER,
-                    bot_group TEXT,
-                    is_not_active INTEGER,
-                    wins INTEGER,
-                    losses INTEGER,
-                    log TEXT,
-                    started_at TEXT,
-                    last_scanned TEXT,
-                    leverage INTEGER,
-                    take_profit_ratio INTEGER,
-                    stop_loss_ratio INTEGER,
-                    is_trailing_stop_active bool,
-                    trailing_stop_activation_point INTEGER,
-                    in_pos INTEGER,
-                    order_type TEXT,
-                    order_created_at TEXT,
-                    order_scanned_at TEXT,
-                    order_quantity INTEGER,
-                    order_capital INTEGER,
-                    order_capital_with_leverage INTEGER,
-                    order_entry_price INTEGER,
-                    order_stop_loss INTEGER,
-                    order_take_profit INTEGER,
-                    order_fee INTEGER,
-                    pnl INTEGER,
-                    roe INTEGER
-                );
-
-
-                CREATE TABLE IF NOT EXISTS bots (
-                    id TEXT PRIMARY KEY,
-                    name TEXT NOT NULL,
-                    capital REAL NOT NULL,
-                    start_time TEXT NOT NULL,
-                    end_time TEXT NOT NULL,
-                    wins INTEGER NOT NULL DEFAULT 0,
-                    losses INTEGER NOT NULL DEFAULT 0
-                );
-
-                CREATE TABLE IF NOT EXISTS orders (
-                    id TEXT PRIMARY KEY,
-                    symbol TEXT NOT NULL,
-                    order_type TEXT NOT NULL,
-                    bot_name TEXT NOT NULL,
-                    entry_price INTEGER NOT NULL,
-                    exit_price INTEGER NOT NULL,
-                    quantity INTEGER NOT NULL,
-                    pnl INTEGER NOT NULL,
-                    roe INTEGER NOT NULL,
-                    created_at TEXT NOT NULL,
-                    closed_at TEXT NOT NULL,
-                    fee INTEGER NOT NULL,
-                    leverage INTEGER NOT NULL
-                );
-            ")?;
-
-
-        // if !column_names.contains(&"losses".to_string()) {
-        //     conn.execute("ALTER TABLE bots ADD COLUMN losses INTEGER NOT NULL", [])?;
-        // }
-        Ok(Repository { path: db_path })
+    /// Opens a pool of WAL connections to the database and brings its schema up to date.
+    pub fn new(db_path: PathBuf) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let manager = SqliteConnectionManager::file(&db_path).with_init(|conn| {
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+            // WAL stays consistent with NORMAL, a crash can only lose the last commits
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+            conn.busy_timeout(Duration::from_secs(5))
+        });
+        let pool = Pool::builder().max_size(POOL_SIZE).build(manager)?;
+        migrate(&mut *pool.get()?)?;
+        Ok(Repository { pool })
     }
 
-    pub fn save_bot_state(&self, bots: Vec<Bot>) -> Result<()>{
-        let conn = Connection::open(&self.path)?;
-        for b in bots {
-            conn.execute(
-                "INSERT INTO bot_state (
-                    name,
-                    symbol,
-                    timeframe,
-                    strategy_name,
-                    capital,
-                    bot_group,
-                    is_not_active,
-                    wins,
-                    losses,
-                    log,
-                    started_at,
-                    last_scanned,
-                    leverage,
-                    take_profit_ratio,
-                    stop_loss_ratio,
-                    is_trailing_stop_active,
-                    trailing_stop_activation_point,
-                    in_pos,
-                    order_type,
-                    order_created_at,
-                    order_scanned_at,
-                    order_quantity,
-                    order_capital,
-                    order_capital_with_leverage,
-                    order_entry_price,
-                    order_stop_loss,
-                    order_take_profit,
-                    order_fee,
-                    pnl,
-                    roe
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30)
-                ON CONFLICT(name)
-                DO UPDATE SET
-                    capital = excluded.capital,
-                    is_not_active = excluded.is_not_active,
-                    wins = excluded.wins,
-                    losses = excluded.losses,
-                    log = excluded.log,
-                    started_at = excluded.started_at,
-                    last_scanned = excluded.last_scanned,
-                    leverage = excluded.leverage,
-                    take_profit_ratio = excluded.take_profit_ratio,
-                    stop_loss_ratio = excluded.stop_loss_ratio,
-                    is_trailing_stop_active = excluded.is_trailing_stop_active,
-                    trailing_stop_activation_point = excluded.trailing_stop_activation_point,
-                    in_pos = excluded.in_pos,
-                    order_type = excluded.order_type,
-                    order_created_at = excluded.order_created_at,
-                    order_scanned_at = excluded.order_scanned_at,
-                    order_quantity = excluded.order_quantity,
-                    order_capital = excluded.order_capital,
-                    order_capital_with_leverage = excluded.order_capital_with_leverage,
-                    order_entry_price = excluded.order_entry_price,
-                    order_stop_loss = excluded.order_stop_loss,
-                    order_take_profit = excluded.order_take_profit,
-                    order_fee = excluded.order_fee,
-                    pnl = excluded.pnl,
-                    roe = excluded.roe;",
-                params![b.name, b.symbol, b.timeframe, b.strategy_name, b.capital, b.group, b.is_not_active, b.wins, b.losses, b.log, b.started_at.to_rfc3339(), b.last_scanned.to_rfc3339(), b.leverage, b.take_profit_ratio, b.stop_loss_ratio, b.is_trailing_stop_active, b.trailing_stop_activation_point, b.in_pos, b.order_type, b.order_created_at.to_rfc3339(), b.order_scanned_at.to_rfc3339(), b.order_quantity, b.order_capital, b.order_capital_with_leverage, b.order_entry_price, b.order_stop_loss, b.order_take_profit, b.order_fee, b.pnl, b.roe],
-            )?;
-        }
-        Ok(())
+    /// Runs `f` with a pooled connection on the blocking thread pool, off the async workers.
+    async fn run<T, F>(&self, f: F) -> Result<T, Box<dyn Error + Send + Sync>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Box<dyn Error + Send + Sync>> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || f(&mut *pool.get()?)).await?
+    }
+
+    /// Upserts every bot in one transaction.
+    pub async fn save_bot_state(&self, bots: Vec<Bot>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            upsert_bots(&tx, bots)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
     }
 
-    pub fn get_bot_state(&self) -> Result<Vec<Bot>> {
-        let conn = Connection::open(&self.path)?;
-        let mut stmt = conn.prepare("SELECT
+    /// Saves the default bots that were never seeded before and returns them, a bot deleted
+    /// through the API stays deleted and one created with the same name is kept as it is.
+    pub async fn seed_bots(&self, bots: Vec<Bot>) -> Result<Vec<Bot>, Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let mut seeded = Vec::new();
+            {
+                let mut known = tx.prepare_cached("SELECT EXISTS (SELECT 1 FROM seeded_bots WHERE name = ?1) OR EXISTS (SELECT 1 FROM bot_state WHERE name = ?1)")?;
+                let mut record = tx.prepare_cached("INSERT OR IGNORE INTO seeded_bots (name) VALUES (?1)")?;
+                for bot in bots {
+                    if !known.query_row([&bot.name], |row| row.get::<_, bool>(0))? {
+                        seeded.push(bot.clone());
+                    }
+                    record.execute([&bot.name])?;
+                }
+            }
+            upsert_bots(&tx, seeded.clone())?;
+            tx.commit()?;
+            Ok(seeded)
+        })
+        .await
+    }
+
+    pub async fn delete_bot_state(&self, name: &str) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let name = name.to_string();
+        self.run(move |conn| {
+            let deleted = conn.prepare_cached("DELETE FROM bot_state WHERE name = ?1")?.execute(params![name])?;
+            Ok(deleted)
+        })
+        .await
+    }
+
+    pub async fn get_bot_state(&self) -> Result<Vec<Bot>, Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT
+                        name,
+                        symbol,
+                        timeframe,
+                        strategy_name,
+                        capital,
+                        bot_group,
+                        is_not_active,
+                        wins,
+                        losses,
+                        log,
+                        started_at,
+                        last_scanned,
+                        leverage,
+                        take_profit_ratio,
+                        stop_loss_ratio,
+                        is_trailing_stop_active,
+                        trailing_stop_activation_point,
+                        in_pos,
+                        order_type,
+                        order_created_at,
+                        order_scanned_at,
+                        order_quantity,
+                        order_capital,
+                        order_capital_with_leverage,
+                        order_entry_price,
+                        order_stop_loss,
+                        order_take_profit,
+                        order_fee,
+                        pnl,
+                        roe,
+                        mode,
+                        entry_order_id,
+                        stop_order_id,
+                        take_profit_order_id,
+                        strategy_params
+                    FROM bot_state")?;
+
+            let bots = stmt.query_map([], |r| {
+                let strategy_name: String = r.get(3)?;
+                let started_at: String = r.get(10)?;
+                let last_scanned: String = r.get(11)?;
+                let order_crated_at: String = r.get(19)?;
+                let order_scanned_at: String = r.get(20)?;
+                let strategy_params: String = r.get(34)?;
+                let name: String = r.get(0)?;
+                let mut strategy_params: Value = serde_json::from_str(&strategy_params).unwrap_or_default();
+                let mut is_not_active: bool = r.get(6)?;
+                let mut log: String = r.get(9)?;
+                // rows from before params existed get the defaults, invalid ones are kept as stored
+                // and the bot is paused until they are fixed, it never trades without its strategy
+                let strategy = match strategy::find_strategy(&strategy_name, &strategy_params) {
+                    Ok(strategy) => {
+                        if strategy_params.is_null() {
+                            strategy_params = strategy.params();
+                        }
+                        Some(strategy)
+                    }
+                    Err(e) => {
+                        error!("bot {} paused, strategy {}: {}", name, strategy_name, e);
+                        is_not_active = true;
+                        log = format!("paused: {}", e);
+                        None
+                    }
+                };
+                Ok(Bot {
                     name,
-                    symbol,
-                    timeframe,
+                    symbol: r.get(1)?,
+                    timeframe: r.get(2)?,
                     strategy_name,
-                    capital,
-                    bot_group,
+                    strategy_params,
+                    strategy,
+                    capital: r.get(4)?,
+                    group: r.get(5)?,
                     is_not_active,
-                    wins,
-                    losses,
+                    mode: r.get(30)?,
+                    wins: r.get(7)?,
+                    losses: r.get(8)?,
                     log,
-                    started_at,
-                    last_scanned,
-                    leverage,
-                    take_profit_ratio,
-                    stop_loss_ratio,
-                    is_trailing_stop_active,
-                    trailing_stop_activation_point,
-                    in_pos,
-                    order_type,
-                    order_created_at,
-                    order_scanned_at,
-                    order_quantity,
-                    order_capital,
-                    order_capital_with_leverage,
-                    order_entry_price,
-                    order_stop_loss,
-                    order_take_profit,
-                    order_fee,
-                    pnl,
-                    roe
-                FROM bot_state")?;
-
-        let bots = stmt.query_map([], |r| {
-            let strategy_name: String = r.get(3)?;
-            let started_at: String = r.get(10)?;
-            let last_scanned: String = r.get(11)?;
-            let order_crated_at: String = r.get(19)?;
-            let order_scanned_at: String = r.get(20)?;
-            let strategy = strategy::get_strategy(&strategy_name);
-            Ok(Bot {
-                name: r.get(0)?,
-                symbol: r.get(1)?,
-                timeframe: r.get(2)?,
-                strategy_name,
-                strategy: Option::from(strategy),
-                capital: r.get(4)?,
-                group: r.get(5)?,
-                is_not_active: r.get(6)?,
-                wins: r.get(7)?,
-                losses: r.get(8)?,
-                log: r.get(9)?,
-                started_at: started_at.parse().unwrap(),
-                last_scanned: last_scanned.parse().unwrap(),
-                leverage: r.get(12)?,
-                take_profit_ratio: r.get(13)?,
-                stop_loss_ratio: r.get(14)?,
-                is_trailing_stop_active: r.get(15)?,
-                trailing_stop_activation_point: r.get(16)?,
-                in_pos: r.get(17)?,
-                order_type: r.get(18)?,
-                order_created_at: order_crated_at.parse().unwrap(),
-                order_scanned_at: order_scanned_at.parse().unwrap(),
-                order_quantity: r.get(21)?,
-                order_capital: r.get(22)?,
-                order_capital_with_leverage: r.get(23)?,
-                order_entry_price: r.get(24)?,
-                order_stop_loss: r.get(25)?,
-                order_take_profit: r.get(26)?,
-                order_fee: r.get(27)?,
-                pnl: r.get(28)?,
-                roe: r.get(29)?,
-            })
-        })?.collect::<Result<Vec<_>, _>>()?;
-
-        Ok(bots)
+                    started_at: started_at.parse().unwrap(),
+                    last_scanned: last_scanned.parse().unwrap(),
+                    leverage: r.get(12)?,
+                    take_profit_ratio: r.get(13)?,
+                    stop_loss_ratio: r.get(14)?,
+                    is_trailing_stop_active: r.get(15)?,
+                    trailing_stop_activation_point: r.get(16)?,
+                    in_pos: r.get(17)?,
+                    order_type: r.get(18)?,
+                    order_created_at: order_crated_at.parse().unwrap(),
+                    order_scanned_at: order_scanned_at.parse().unwrap(),
+                    order_quantity: r.get(21)?,
+                    order_capital: r.get(22)?,
+                    order_capital_with_leverage: r.get(23)?,
+                    order_entry_price: r.get(24)?,
+                    order_stop_loss: r.get(25)?,
+                    order_take_profit: r.get(26)?,
+                    order_fee: r.get(27)?,
+                    pnl: r.get(28)?,
+                    roe: r.get(29)?,
+                    entry_order_id: r.get(31)?,
+                    stop_order_id: r.get(32)?,
+                    take_profit_order_id: r.get(33)?,
+                    deleted: false,
+                })
+            })?.collect::<Result<Vec<_>, _>>()?;
+
+            Ok(bots)
+        })
+        .await
     }
 
     #[allow(dead_code)]
-    pub fn create_bot(&self, bot: &Bot) -> Result<usize> {
-        let conn = Connection::open(&self.path)?;
-        let now = tools::get_date(3);
-        let id = format!("{}_{}", bot.name, bot.started_at);
-        conn.execute(
-            "INSERT INTO bots (id, name, capital, wins, losses, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![id, bot.name, bot.capital + bot.order_capital, bot.wins, bot.losses, bot.started_at.to_rfc3339(), now.to_rfc3339()],
-        )
+    pub async fn create_bot(&self, bot: &Bot) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let bot = bot.clone();
+        self.run(move |conn| {
+            let now = tools::get_date(config::get().time_zone);
+            let id = format!("{}_{}", bot.name, bot.started_at);
+            let mut stmt = conn.prepare_cached("INSERT INTO bots (id, name, capital, wins, losses, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            let inserted = stmt.execute(params![id, bot.name, bot.capital + bot.order_capital, bot.wins, bot.losses, bot.started_at.to_rfc3339(), now.to_rfc3339()])?;
+            Ok(inserted)
+        })
+        .await
     }
 
-    pub fn create_bots_in_batch(&self, bots: &mut Vec<Bot>) -> Result<()> {
-        let mut conn = Connection::open(&self.path)?;
-        let now = tools::get_date(3);
+    pub async fn create_bots_in_batch(&self, bots: Vec<Bot>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let now = tools::get_date(config::get().time_zone);
 
-        let tx = conn.transaction()?;
-        {
-            let mut stmt = tx.prepare("INSERT INTO bots (id, name, capital, wins, losses, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached("INSERT INTO bots (id, name, capital, wins, losses, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
 
-            for b in bots {
-                let id = format!("{}_{}", b.name, b.started_at);
-                stmt.execute(params![id, b.name, b.capital + b.order_capital, b.wins, b.losses, b.started_at.to_rfc3339(), now.to_rfc3339()])?;
+                for b in bots {
+                    let id = format!("{}_{}", b.name, b.started_at);
+                    stmt.execute(params![id, b.name, b.capital + b.order_capital, b.wins, b.losses, b.started_at.to_rfc3339(), now.to_rfc3339()])?;
+                }
             }
-        }
 
-        tx.commit()?;
+            tx.commit()?;
 
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
-    pub fn get_bot(&self, bot_name: String) -> Result<Vec<StatisticResult>> {
-        let conn = Connection::open(&self.path)?;
-        let mut stmt = conn.prepare("SELECT  name, capital, wins, losses, start_time, end_time FROM bots WHERE name = ?1")?;
-        let bots = stmt.query_map([bot_name], |row| {
-            let start_time: String = row.get(4)?;
-            let end_time: String = row.get(5)?;
-            let s = start_time.parse().unwrap();
-
-            Ok(StatisticResult {
-                name: row.get(0)?,
-                capital: row.get(1)?,
-                wins: row.get(2)?,
-                losses: row.get(3)?,
-                start_time: s,
-                end_time: end_time.parse().unwrap(),
-            })
-        })?
-          .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(bots)
+    pub async fn get_bot(&self, bot_name: String) -> Result<Vec<StatisticResult>, Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT  name, capital, wins, losses, start_time, end_time FROM bots WHERE name = ?1")?;
+            let bots = stmt.query_map([bot_name], |row| {
+                let start_time: String = row.get(4)?;
+                let end_time: String = row.get(5)?;
+                let s = start_time.parse().unwrap();
+
+                Ok(StatisticResult {
+                    name: row.get(0)?,
+                    capital: row.get(1)?,
+                    wins: row.get(2)?,
+                    losses: row.get(3)?,
+                    start_time: s,
+                    end_time: end_time.parse().unwrap(),
+                })
+            })?
+              .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(bots)
+        })
+        .await
     }
 
-    pub fn get_all_bots(&self) -> Result<Vec<StatisticResult>> {
-        let conn = Connection::open(&self.path)?;
-        let mut stmt = conn.prepare("SELECT name, capital, wins, losses, start_time, end_time FROM bots")?;
-        let bots = stmt.query_map([], |row| {
-            let start_time: String = row.get(4)?;
-            let end_time: String = row.get(5)?;
-            let s = start_time.parse().unwrap();
-
-            Ok(StatisticResult {
-                name: row.get(0)?,
-                capital: row.get(1)?,
-                wins: row.get(2)?,
-                losses: row.get(3)?,
-                start_time: s,
-                end_time: end_time.parse().unwrap(),
-            })
-        })?
-          .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(bots)
+    pub async fn get_all_bots(&self) -> Result<Vec<StatisticResult>, Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT name, capital, wins, losses, start_time, end_time FROM bots")?;
+            let bots = stmt.query_map([], |row| {
+                let start_time: String = row.get(4)?;
+                let end_time: String = row.get(5)?;
+                let s = start_time.parse().unwrap();
+
+                Ok(StatisticResult {
+                    name: row.get(0)?,
+                    capital: row.get(1)?,
+                    wins: row.get(2)?,
+                    losses: row.get(3)?,
+                    start_time: s,
+                    end_time: end_time.parse().unwrap(),
+                })
+            })?
+              .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(bots)
+        })
+        .await
     }
 
-    pub fn get_orders_in_range(&self, bot_name: String, start_time: DateTime<FixedOffset>, end_time: DateTime<FixedOffset>) -> Result<Vec<Order>> {
-        let conn = Connection::open(&self.path)?;
-        let mut stmt = conn.prepare("SELECT symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe, created_at, closed_at, fee, leverage FROM orders WHERE bot_name = ?1 AND created_at >= ?2 AND closed_at <= ?3")?;
-        let orders = stmt.query_map([bot_name, start_time.to_rfc3339(), end_time.to_rfc3339()], |row| {
-            let created_at: String = row.get(8)?;
-            let closed_at: String = row.get(9)?;
-            Ok(Order {
-                symbol: row.get(0)?,
-                order_type: row.get(1)?,
-                bot_name: row.get(2)?,
-                entry_price: row.get(3)?,
-                exit_price: row.get(4)?,
-                quantity: row.get(5)?,
-                pnl: row.get(6)?,
-                roe: row.get(7)?,
-                created_at: created_at.parse().unwrap(),
-                closed_at: closed_at.parse().unwrap(),
-                fee: row.get(10)?,
-                leverage: row.get(11)?,
-
-            })
-        })?
-          .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(orders)
+    pub async fn get_orders_in_range(&self, bot_name: String, start_time: DateTime<FixedOffset>, end_time: DateTime<FixedOffset>) -> Result<Vec<Order>, Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe, created_at, closed_at, fee, leverage, entry_order_id, exit_order_id FROM orders WHERE bot_name = ?1 AND created_at >= ?2 AND closed_at <= ?3")?;
+            let orders = stmt.query_map([bot_name, start_time.to_rfc3339(), end_time.to_rfc3339()], |row| {
+                let created_at: String = row.get(8)?;
+                let closed_at: String = row.get(9)?;
+                Ok(Order {
+                    symbol: row.get(0)?,
+                    order_type: row.get(1)?,
+                    bot_name: row.get(2)?,
+                    entry_price: row.get(3)?,
+                    exit_price: row.get(4)?,
+                    quantity: row.get(5)?,
+                    pnl: row.get(6)?,
+                    roe: row.get(7)?,
+                    created_at: created_at.parse().unwrap(),
+                    closed_at: closed_at.parse().unwrap(),
+                    fee: row.get(10)?,
+                    leverage: row.get(11)?,
+                    entry_order_id: row.get(12)?,
+                    exit_order_id: row.get(13)?,
+                })
+            })?
+              .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(orders)
+        })
+        .await
     }
 
-    pub fn create_orders(&self, orders: &Vec<Order>) -> Result<()> {
-        let mut conn = Connection::open(&self.path)?;
-
+    pub async fn create_orders(&self, orders: Vec<Order>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            insert_orders(&tx, orders)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
 
-        let tx = conn.transaction()?;
-        {
-            let mut stmt = tx.prepare("INSERT INTO orders (id, symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe, created_at, closed_at, fee, leverage) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)")?;
+    /// Records closed orders together with the bots they left, so a crash can't replay a close.
+    pub async fn save_closed_positions(&self, orders: Vec<Order>, bots: Vec<Bot>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            insert_orders(&tx, orders)?;
+            upsert_bots(&tx, bots)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
 
-            for o in orders {
-                let id = format!("{}_{}", o.bot_name, o.created_at);
-                stmt.execute(params![id, o.symbol, o.order_type, o.bot_name, o.entry_price, o.exit_price, o.quantity, o.pnl, o.roe, o.created_at.to_rfc3339(), o.closed_at.to_rfc3339(), o.fee, o.leverage])?;
-            }
-        }
+    pub async fn get_order_by_bot_name(&self, bot_name: String) -> Result<Vec<Order>, Box<dyn Error + Send + Sync>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe, created_at, closed_at, fee, leverage, entry_order_id, exit_order_id FROM orders WHERE bot_name = ?1")?;
+            let orders = stmt.query_map([bot_name], |row| {
+                let created_at: String = row.get(8)?;
+                let closed_at: String = row.get(9)?;
+                Ok(Order {
+                    symbol: row.get(0)?,
+                    order_type: row.get(1)?,
+                    bot_name: row.get(2)?,
+                    entry_price: row.get(3)?,
+                    exit_price: row.get(4)?,
+                    quantity: row.get(5)?,
+                    pnl: row.get(6)?,
+                    roe: row.get(7)?,
+                    created_at: created_at.parse().unwrap(),
+                    closed_at: closed_at.parse().unwrap(),
+                    fee: row.get(10)?,
+                    leverage: row.get(11)?,
+                    entry_order_id: row.get(12)?,
+                    exit_order_id: row.get(13)?,
+                })
+            })?
+              .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(orders)
+        })
+        .await
+    }
 
-        tx.commit()?;
+}
 
-        Ok(())
+fn upsert_bots(conn: &Connection, bots: Vec<Bot>) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO bot_state (
+            name,
+            symbol,
+            timeframe,
+            strategy_name,
+            capital,
+            bot_group,
+            is_not_active,
+            wins,
+            losses,
+            log,
+            started_at,
+            last_scanned,
+            leverage,
+            take_profit_ratio,
+            stop_loss_ratio,
+            is_trailing_stop_active,
+            trailing_stop_activation_point,
+            in_pos,
+            order_type,
+            order_created_at,
+            order_scanned_at,
+            order_quantity,
+            order_capital,
+            order_capital_with_leverage,
+            order_entry_price,
+            order_stop_loss,
+            order_take_profit,
+            order_fee,
+            pnl,
+            roe,
+            mode,
+            entry_order_id,
+            stop_order_id,
+            take_profit_order_id,
+            strategy_params
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35)
+        ON CONFLICT(name)
+        DO UPDATE SET
+            capital = excluded.capital,
+            is_not_active = excluded.is_not_active,
+            wins = excluded.wins,
+            losses = excluded.losses,
+            log = excluded.log,
+            started_at = excluded.started_at,
+            last_scanned = excluded.last_scanned,
+            leverage = excluded.leverage,
+            take_profit_ratio = excluded.take_profit_ratio,
+            stop_loss_ratio = excluded.stop_loss_ratio,
+            is_trailing_stop_active = excluded.is_trailing_stop_active,
+            trailing_stop_activation_point = excluded.trailing_stop_activation_point,
+            in_pos = excluded.in_pos,
+            order_type = excluded.order_type,
+            order_created_at = excluded.order_created_at,
+            order_scanned_at = excluded.order_scanned_at,
+            order_quantity = excluded.order_quantity,
+            order_capital = excluded.order_capital,
+            order_capital_with_leverage = excluded.order_capital_with_leverage,
+            order_entry_price = excluded.order_entry_price,
+            order_stop_loss = excluded.order_stop_loss,
+            order_take_profit = excluded.order_take_profit,
+            order_fee = excluded.order_fee,
+            pnl = excluded.pnl,
+            roe = excluded.roe,
+            mode = excluded.mode,
+            entry_order_id = excluded.entry_order_id,
+            stop_order_id = excluded.stop_order_id,
+            take_profit_order_id = excluded.take_profit_order_id,
+            strategy_params = excluded.strategy_params;",
+    )?;
+
+    // a manager that still held a deleted bot's handle must not bring its row back
+    for b in bots.iter().filter(|b| !b.deleted) {
+        stmt.execute(params![b.name, b.symbol, b.timeframe, b.strategy_name, b.capital, b.group, b.is_not_active, b.wins, b.losses, b.log, b.started_at.to_rfc3339(), b.last_scanned.to_rfc3339(), b.leverage, b.take_profit_ratio, b.stop_loss_ratio, b.is_trailing_stop_active, b.trailing_stop_activation_point, b.in_pos, b.order_type, b.order_created_at.to_rfc3339(), b.order_scanned_at.to_rfc3339(), b.order_quantity, b.order_capital, b.order_capital_with_leverage, b.order_entry_price, b.order_stop_loss, b.order_take_profit, b.order_fee, b.pnl, b.roe, b.mode, b.entry_order_id, b.stop_order_id, b.take_profit_order_id, b.strategy_params.to_string()])?;
     }
+    Ok(())
+}
+
+fn insert_orders(conn: &Connection, orders: Vec<Order>) -> Result<()> {
+    let mut stmt = conn.prepare_cached("INSERT INTO orders (id, symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe, created_at, closed_at, fee, leverage, entry_order_id, exit_order_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)")?;
 
-    pub fn get_order_by_bot_name(&self, bot_name: String) -> Result<Vec<Order>> {
-        let conn = Connection::open(&self.path)?;
-        let mut stmt = conn.prepare("SELECT symbol, order_type, bot_name, entry_price, exit_price, quantity, pnl, roe, created_at, closed_at, fee, leverage FROM orders WHERE bot_name = ?1")?;
-        let orders = stmt.query_map([bot_name], |row| {
-            let created_at: String = row.get(8)?;
-            let closed_at: String = row.get(9)?;
-            Ok(Order {
-                symbol: row.get(0)?,
-                order_type: row.get(1)?,
-                bot_name: row.get(2)?,
-                entry_price: row.get(3)?,
-                exit_price: row.get(4)?,
-                quantity: row.get(5)?,
-                pnl: row.get(6)?,
-                roe: row.get(7)?,
-                created_at: created_at.parse().unwrap(),
-                closed_at: closed_at.parse().unwrap(),
-                fee: row.get(10)?,
-                leverage: row.get(11)?,
-
-            })
-        })?
-          .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(orders)
+    for o in orders {
+        let id = format!("{}_{}", o.bot_name, o.created_at);
+        stmt.execute(params![id, o.symbol, o.order_type, o.bot_name, o.entry_price, o.exit_price, o.quantity, o.pnl, o.roe, o.created_at.to_rfc3339(), o.closed_at.to_rfc3339(), o.fee, o.leverage, o.entry_order_id, o.exit_order_id])?;
     }
+    Ok(())
 }
 
-#[allow(dead_code)]
-fn drop_table(conn: &Connection) -> Result<()> {
-    let mut stmt = conn.prepare("PRAGMA table_info(bots)")?;
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
     let column_names: Vec<String> = stmt.query_map([], |row|
       row.get(1))?
       .collect::<Result<Vec<String>, _>>()?;
 
-    if !column_names.contains(&"wins".to_string()) {
-        conn.execute("DROP TABLE IF EXISTS bots", [])?;
+    if !column_names.iter().any(|c| c == column) {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
     }
     Ok(())
 }
+
+struct Migration {
+    version: u32,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// Forward only, in version order. Never edit a released one, add the next version instead.
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "initial schema", sql: include_str!("../migration/0001_initial.sql") },
+    Migration { version: 2, description: "real columns for prices and amounts", sql: include_str!("../migration/0002_real_columns.sql") },
+    Migration { version: 3, description: "seeded bots", sql: include_str!("../migration/0003_seeded_bots.sql") },
+];
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+}
+
+/// Applies the pending migrations, each in its own transaction with its version row.
+///
+/// Fails without touching anything when the database was written by a newer build.
+fn migrate(conn: &mut Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    conn.execute_batch("
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            );
+        ")?;
+
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(format!("database schema version {} is newer than {}, the latest this build knows, refusing to start", current, SCHEMA_VERSION).into());
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        if migration.version == 1 {
+            add_legacy_columns(&tx)?;
+        }
+        tx.execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.description, tools::get_date(config::get().time_zone).to_rfc3339()],
+        )?;
+        tx.commit()?;
+        info!("database migrated to schema version {}: {}", migration.version, migration.description);
+    }
+    Ok(())
+}
+
+// unversioned builds added these on startup, older databases may still lack some of them
+fn add_legacy_columns(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "bot_state", "mode", "TEXT NOT NULL DEFAULT 'Paper'")?;
+    add_column_if_missing(conn, "bot_state", "entry_order_id", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "bot_state", "stop_order_id", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "bot_state", "take_profit_order_id", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "bot_state", "strategy_params", "TEXT NOT NULL DEFAULT 'null'")?;
+    add_column_if_missing(conn, "orders", "entry_order_id", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "orders", "exit_order_id", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{OrderCommand, Symbol, Timeframe};
+    use crate::models::models::StrategyContainer;
+
+    fn db_path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("traderrs_repository_{}_{}.sqlite", test, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn column_type(conn: &Connection, table: &str, column: &str) -> String {
+        conn.query_row(&format!("SELECT type FROM pragma_table_info('{}') WHERE name = ?1", table), [column], |row| row.get(0)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn fresh_database_is_at_the_latest_version() {
+        let path = db_path("fresh");
+        Repository::new(path.clone()).unwrap();
+        Repository::new(path.clone()).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let applied: u32 = conn.query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(applied as usize, MIGRATIONS.len());
+        assert_eq!(column_type(&conn, "orders", "pnl"), "REAL");
+        assert_eq!(column_type(&conn, "bot_state", "order_entry_price"), "REAL");
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+    }
+
+    #[tokio::test]
+    async fn legacy_database_keeps_its_history() {
+        let path = db_path("legacy");
+        let bot = Bot::new(Timeframe::Min5, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 12.5, 10.0, 0.8, 0.4, 0.1);
+        {
+            // as the first unversioned builds created it, before the order id and params columns
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch("
+                CREATE TABLE bot_state (name TEXT PRIMARY KEY, symbol TEXT, timeframe TEXT, strategy_name TEXT, capital INTEGER,
+                    bot_group TEXT, is_not_active INTEGER, wins INTEGER, losses INTEGER, log TEXT, started_at TEXT, last_scanned TEXT,
+                    leverage INTEGER, take_profit_ratio INTEGER, stop_loss_ratio INTEGER, is_trailing_stop_active bool,
+                    trailing_stop_activation_point INTEGER, in_pos INTEGER, order_type TEXT, order_created_at TEXT, order_scanned_at TEXT,
+                    order_quantity INTEGER, order_capital INTEGER, order_capital_with_leverage INTEGER, order_entry_price INTEGER,
+                    order_stop_loss INTEGER, order_take_profit INTEGER, order_fee INTEGER, pnl INTEGER, roe INTEGER);
+                CREATE TABLE orders (id TEXT PRIMARY KEY, symbol TEXT NOT NULL, order_type TEXT NOT NULL, bot_name TEXT NOT NULL,
+                    entry_price INTEGER NOT NULL, exit_price INTEGER NOT NULL, quantity INTEGER NOT NULL, pnl INTEGER NOT NULL,
+                    roe INTEGER NOT NULL, created_at TEXT NOT NULL, closed_at TEXT NOT NULL, fee INTEGER NOT NULL, leverage INTEGER NOT NULL);
+                INSERT INTO orders VALUES ('o1', 'SOLUSDT', 'Long', 'EmaMacd_5m_Sol', 100, 101.5, 2, 3, 1.5,
+                    '2025-01-01T00:00:00+00:00', '2025-01-01T01:00:00+00:00', 0.25, 10);
+            ").unwrap();
+            let b = &bot;
+            conn.execute(
+                "INSERT INTO bot_state VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30)",
+                params![b.name, b.symbol, b.timeframe, b.strategy_name, b.capital, b.group, b.is_not_active, b.wins, b.losses, b.log, b.started_at.to_rfc3339(), b.last_scanned.to_rfc3339(), b.leverage, b.take_profit_ratio, b.stop_loss_ratio, b.is_trailing_stop_active, b.trailing_stop_activation_point, b.in_pos, b.order_type, b.order_created_at.to_rfc3339(), b.order_scanned_at.to_rfc3339(), b.order_quantity, b.order_capital, b.order_capital_with_leverage, b.order_entry_price, b.order_stop_loss, b.order_take_profit, b.order_fee, b.pnl, b.roe],
+            ).unwrap();
+        }
+
+        let repository = Repository::new(path.clone()).unwrap();
+        assert!(repository.seed_bots(vec![bot.clone()]).await.unwrap().is_empty());
+        let bots = repository.get_bot_state().await.unwrap();
+        assert_eq!((bots.len(), bots[0].name.as_str(), bots[0].capital), (1, bot.name.as_str(), 12.5));
+        assert_eq!(bots[0].strategy_params, bot.strategy_params);
+
+        let orders = repository.get_order_by_bot_name("EmaMacd_5m_Sol".to_string()).await.unwrap();
+        assert_eq!((orders[0].exit_price, orders[0].pnl, orders[0].fee, orders[0].exit_order_id), (101.5, 3.0, 0.25, 0));
+
+        // integral values were stored as integers, the rebuilt table holds them as reals
+        let conn = Connection::open(&path).unwrap();
+        let stored: String = conn.query_row("SELECT typeof(pnl) FROM orders", [], |row| row.get(0)).unwrap();
+        assert_eq!(stored, "real");
+    }
+
+    #[tokio::test]
+    async fn default_bots_are_seeded_once() {
+        let path = db_path("seeded");
+        let repository = Repository::new(path.clone()).unwrap();
+        let sol = Bot::new(Timeframe::Min5, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        let eth = Bot::new(Timeframe::Min5, Symbol::new("ETHUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+
+        assert_eq!(repository.seed_bots(vec![sol.clone()]).await.unwrap().len(), 1);
+        assert_eq!(repository.delete_bot_state(&sol.name).await.unwrap(), 1);
+        drop(repository);
+
+        // a deleted bot stays deleted, a symbol added to the config gets its bots
+        let repository = Repository::new(path).unwrap();
+        let seeded = repository.seed_bots(vec![sol, eth.clone()]).await.unwrap();
+        assert_eq!(seeded.iter().map(|b| b.name.as_str()).collect::<Vec<_>>(), vec![eth.name.as_str()]);
+        let names: Vec<String> = repository.get_bot_state().await.unwrap().into_iter().map(|b| b.name).collect();
+        assert_eq!(names, vec![eth.name]);
+    }
+
+    #[tokio::test]
+    async fn closed_positions_are_saved_with_their_bots() {
+        let repository = Repository::new(db_path("closed")).unwrap();
+        let mut bot = Bot::new(Timeframe::Min5, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        repository.save_bot_state(vec![bot.clone()]).await.unwrap();
+
+        bot.capital = 101.5;
+        bot.wins = 1;
+        let mut order = Order::dummy();
+        order.bot_name = bot.name.clone();
+        repository.save_closed_positions(vec![order], vec![bot.clone()]).await.unwrap();
+
+        let bots = repository.get_bot_state().await.unwrap();
+        assert_eq!((bots[0].capital, bots[0].wins), (101.5, 1));
+        assert_eq!(repository.get_order_by_bot_name(bot.name).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn bot_with_invalid_params_is_paused() {
+        let path = db_path("invalid_params");
+        let repository = Repository::new(path.clone()).unwrap();
+        let bot = Bot::new(Timeframe::Min5, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        repository.save_bot_state(vec![bot.clone()]).await.unwrap();
+        Connection::open(&path).unwrap().execute("UPDATE bot_state SET strategy_params = '{\"trend_ema\": 500}'", []).unwrap();
+
+        let loaded = repository.get_bot_state().await.unwrap().remove(0);
+        assert!(loaded.is_not_active && loaded.strategy.is_none(), "{}", loaded.log);
+        assert_eq!(loaded.strategy_params["trend_ema"], 500);
+        assert_eq!(loaded.run_strategy(&StrategyContainer::new()).0, OrderCommand::Wait);
+        let mut loaded = loaded;
+        assert!(loaded.open_position_at(&OrderCommand::Long, 100.0, bot.started_at).is_err());
+        assert!(!loaded.in_pos);
+    }
+
+    #[tokio::test]
+    async fn newer_schema_is_refused() {
+        let path = db_path("newer");
+        Repository::new(path.clone()).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        conn.execute("INSERT INTO schema_version (version, description, applied_at) VALUES (?1, 'from the future', '')", [SCHEMA_VERSION + 1]).unwrap();
+
+        let err = Repository::new(path).unwrap_err();
+        assert!(err.to_string().contains("newer"), "{}", err);
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION + 1);
+    }
+}
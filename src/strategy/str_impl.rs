@@ -1,39 +1,92 @@
 use crate::enums::OrderCommand::{Long, Short, Wait};
 use crate::enums::{OrderCommand, Symbol, Timeframe};
 use crate::models::models::StrategyContainer;
-use crate::strategy::strategy::Strategy;
+use crate::indicators::IndicatorSpec;
+use crate::strategy::strategy::{check_period, Strategy, StrategyParams};
 use crate::tools::get_close_prices;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmaMacdParams {
+    /// Trades only in the direction of this EMA.
+    pub trend_ema: usize,
+    pub macd_fast: usize,
+    pub macd_slow: usize,
+    pub macd_signal: usize,
+}
+
+impl Default for EmaMacdParams {
+    fn default() -> Self {
+        Self { trend_ema: 200, macd_fast: 12, macd_slow: 26, macd_signal: 9 }
+    }
+}
+
+impl EmaMacdParams {
+    fn macd(&self) -> IndicatorSpec {
+        IndicatorSpec::Macd { fast: self.macd_fast, slow: self.macd_slow, signal: self.macd_signal }
+    }
+}
+
+impl StrategyParams for EmaMacdParams {
+    fn validate(&self) -> Result<(), String> {
+        check_period("trend_ema", self.trend_ema)?;
+        check_period("macd_fast", self.macd_fast)?;
+        check_period("macd_slow", self.macd_slow)?;
+        check_period("macd_signal", self.macd_signal)?;
+        if self.macd_fast >= self.macd_slow {
+            return Err("macd_fast must be shorter than macd_slow".to_string());
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct EmaMacd {}
+pub struct EmaMacd {
+    params: EmaMacdParams,
+}
+impl EmaMacd {
+    pub fn new(params: EmaMacdParams) -> Self {
+        Self { params }
+    }
+}
 impl Strategy for EmaMacd {
     fn name(&self) -> &str {
         "EmaMacd"
     }
 
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or_default()
+    }
+
+    fn indicators(&self) -> Vec<IndicatorSpec> {
+        vec![self.params.macd(), IndicatorSpec::Ema { period: self.params.trend_ema }]
+    }
+
     fn run(&self, sc: &StrategyContainer, timeframe: &Timeframe, symbol: &Symbol) -> (OrderCommand, String) {
-        let option_candles = sc.candles_map.get(&(*timeframe, *symbol));
+        let option_candles = sc.get_candles(timeframe, symbol);
         if option_candles.is_none() {
             return (Wait, "no candles".to_string());
         }
 
         let candles = option_candles.unwrap();
 
-        if candles.is_empty() {
+        if candles.len() < 2 {
             return (Wait, "no candles".to_string());
         }
 
-        let macd_data = if let Some(val) = sc.get_macd(timeframe, symbol) {
+        let p = &self.params;
+        let macd_data = if let Some(val) = sc.get_macd(timeframe, symbol, p.macd_fast, p.macd_slow, p.macd_signal) {
             val
         } else { return (Wait, "No macd".to_string()) };
 
-        let ema200 = if let Some(val) = sc.get_ema(timeframe, symbol, 200) {
+        let ema = if let Some(val) = sc.get_ema(timeframe, symbol, self.params.trend_ema) {
             val[val.len() - 1]
         } else { return (Wait, "No Ema".to_string()) };
 
         let closes = get_close_prices(candles);
 
-
         let n = macd_data.macd.len();
         let macd = macd_data.macd[n - 1];
         let macd_prev = macd_data.macd[n - 2];
@@ -43,12 +96,12 @@ impl Strategy for EmaMacd {
 
         let info = format!(
             "p:{:.2}, mc:{:.2}, sg:{:.2}, em:{:.2}",
-            price, macd, signal, ema200
+            price, macd, signal, ema
         );
         
-        if macd_prev < 0.0 && macd > 0.0 && hist > 0.0 && price > ema200 {
+        if macd_prev < 0.0 && macd > 0.0 && hist > 0.0 && price > ema {
             (Long, info)
-        } else if macd_prev > 0.0 && macd < 0.0 && hist < 0.0 && price < ema200 {
+        } else if macd_prev > 0.0 && macd < 0.0 && hist < 0.0 && price < ema {
             (Short, info)
         } else {
             (Wait, info)
@@ -57,28 +110,45 @@ impl Strategy for EmaMacd {
 }
 
 #[derive(Debug, Clone)]
-pub struct EmaMacd2 {}
+pub struct EmaMacd2 {
+    params: EmaMacdParams,
+}
+impl EmaMacd2 {
+    pub fn new(params: EmaMacdParams) -> Self {
+        Self { params }
+    }
+}
 impl Strategy for EmaMacd2 {
     fn name(&self) -> &str {
         "EmaMacd2"
     }
+
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or_default()
+    }
+
+    fn indicators(&self) -> Vec<IndicatorSpec> {
+        vec![self.params.macd(), IndicatorSpec::Ema { period: self.params.trend_ema }]
+    }
+
     fn run(&self, sc: &StrategyContainer, timeframe: &Timeframe, symbol: &Symbol) -> (OrderCommand, String) {
-        let option_candles = sc.candles_map.get(&(*timeframe, *symbol));
+        let option_candles = sc.get_candles(timeframe, symbol);
         if option_candles.is_none() {
             return (Wait, "no candles".to_string());
         }
 
         let candles = option_candles.unwrap();
 
-        if candles.is_empty() {
+        if candles.len() < 2 {
             return (Wait, "no candles".to_string());
         }
 
-        let macd_data = if let Some(val) = sc.get_macd(timeframe, symbol) {
+        let p = &self.params;
+        let macd_data = if let Some(val) = sc.get_macd(timeframe, symbol, p.macd_fast, p.macd_slow, p.macd_signal) {
             val
         } else { return (Wait, "No macd".to_string()) };
 
-        let ema200 = if let Some(val) = sc.get_ema(timeframe, symbol, 200) {
+        let ema = if let Some(val) = sc.get_ema(timeframe, symbol, self.params.trend_ema) {
             val[val.len() - 1]
         } else { return (Wait, "No Ema".to_string()) };
 
@@ -94,12 +164,12 @@ impl Strategy for EmaMacd2 {
 
         let info = format!(
             "p:{:.2}, mc:{:.2}, sg:{:.2}, em:{:.2}",
-            price, macd, signal, ema200
+            price, macd, signal, ema
         );
 
-        if macd_prev < signal_prev && macd > signal && price > ema200 {
+        if macd_prev < signal_prev && macd > signal && price > ema {
             (Long, info)
-        } else if macd_prev > signal_prev && macd < signal && price < ema200 {
+        } else if macd_prev > signal_prev && macd < signal && price < ema {
             (Short, info)
         } else {
             (Wait, info)
@@ -107,45 +177,82 @@ impl Strategy for EmaMacd2 {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmaBounceParams {
+    pub fast_ema: usize,
+    pub slow_ema: usize,
+}
+
+impl Default for EmaBounceParams {
+    fn default() -> Self {
+        Self { fast_ema: 50, slow_ema: 200 }
+    }
+}
+
+impl StrategyParams for EmaBounceParams {
+    fn validate(&self) -> Result<(), String> {
+        check_period("fast_ema", self.fast_ema)?;
+        check_period("slow_ema", self.slow_ema)?;
+        if self.fast_ema >= self.slow_ema {
+            return Err("fast_ema must be shorter than slow_ema".to_string());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct EmaBounce {}
+pub struct EmaBounce {
+    params: EmaBounceParams,
+}
+impl EmaBounce {
+    pub fn new(params: EmaBounceParams) -> Self {
+        Self { params }
+    }
+}
 impl Strategy for EmaBounce {
     fn name(&self) -> &str {
         "EmaBounce"
     }
 
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or_default()
+    }
+
+    fn indicators(&self) -> Vec<IndicatorSpec> {
+        vec![IndicatorSpec::Ema { period: self.params.fast_ema }, IndicatorSpec::Ema { period: self.params.slow_ema }]
+    }
+
     fn run(&self, sc: &StrategyContainer, timeframe: &Timeframe, symbol: &Symbol) -> (OrderCommand, String) {
-        let option_candles = sc.candles_map.get(&(*timeframe, *symbol));
+        let option_candles = sc.get_candles(timeframe, symbol);
         if option_candles.is_none() {
             return (Wait, "no candles".to_string());
         }
 
         let candles = option_candles.unwrap();
 
-        if candles.is_empty() {
+        if candles.len() < 2 {
             return (Wait, "no candles".to_string());
         }
 
-
-        let ema200 = if let Some(val) = sc.get_ema(timeframe, symbol, 200) {
+        let slow = if let Some(val) = sc.get_ema(timeframe, symbol, self.params.slow_ema) {
             val[val.len() - 1]
-        } else { return (Wait, "No Ema 200".to_string()) };
+        } else { return (Wait, format!("No Ema {}", self.params.slow_ema)) };
 
-        let ema50 = if let Some(val) = sc.get_ema(timeframe, symbol, 50) {
+        let fast = if let Some(val) = sc.get_ema(timeframe, symbol, self.params.fast_ema) {
             val[val.len() - 1]
-        } else { return (Wait, "No Ema 50".to_string()) };
+        } else { return (Wait, format!("No Ema {}", self.params.fast_ema)) };
 
         let closes = get_close_prices(candles);
-
-
         let n = closes.len();
+
         let price = closes[n - 1];
-        let info = format!("ema50: {:.2}, ema200: {:.2}", ema50, ema200);
+        let info = format!("ema{}: {:.2}, ema{}: {:.2}", self.params.fast_ema, fast, self.params.slow_ema, slow);
         let prev_price = closes[n - 2];
 
-        if ema50 > ema200 && prev_price < ema50 && price > ema50 {
+        if fast > slow && prev_price < fast && price > fast {
             return (Long, info);
-        } else if ema50 < ema200 && prev_price > ema50 && price < ema50 {
+        } else if fast < slow && prev_price > fast && price < fast {
             return (Short, info)
         }
 
@@ -153,37 +260,94 @@ impl Strategy for EmaBounce {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StocBorderParams {
+    pub period_k: usize,
+    pub smooth_k: usize,
+    pub period_d: usize,
+    /// %D crossing up through this level is a long.
+    pub oversold: f64,
+    /// %D crossing down through this level is a short.
+    pub overbought: f64,
+    pub trend_ema: usize,
+}
+
+impl Default for StocBorderParams {
+    fn default() -> Self {
+        Self {
+            period_k: 14,
+            smooth_k: 1,
+            period_d: 3,
+            oversold: 20.0,
+            overbought: 80.0,
+            trend_ema: 200,
+        }
+    }
+}
+
+impl StrategyParams for StocBorderParams {
+    fn validate(&self) -> Result<(), String> {
+        check_period("period_k", self.period_k)?;
+        check_period("smooth_k", self.smooth_k)?;
+        check_period("period_d", self.period_d)?;
+        check_period("trend_ema", self.trend_ema)?;
+        if !(0.0 < self.oversold && self.oversold < self.overbought && self.overbought < 100.0) {
+            return Err("levels must satisfy 0 < oversold < overbought < 100".to_string());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct StocBorder {}
+pub struct StocBorder {
+    params: StocBorderParams,
+}
+impl StocBorder {
+    pub fn new(params: StocBorderParams) -> Self {
+        Self { params }
+    }
+}
 impl Strategy for StocBorder {
     fn name(&self) -> &str {
         "StocBorder"
     }
 
+    fn params(&self) -> Value {
+        serde_json::to_value(&self.params).unwrap_or_default()
+    }
+
+    fn indicators(&self) -> Vec<IndicatorSpec> {
+        let p = &self.params;
+        vec![
+            IndicatorSpec::Stochastic { period_k: p.period_k, smooth_k: p.smooth_k, period_d: p.period_d },
+            IndicatorSpec::Ema { period: p.trend_ema },
+        ]
+    }
+
     fn run(&self, sc: &StrategyContainer, timeframe: &Timeframe, symbol: &Symbol) -> (OrderCommand, String) {
-        let option_candles = sc.candles_map.get(&(*timeframe, *symbol));
+        let option_candles = sc.get_candles(timeframe, symbol);
         if option_candles.is_none() {
             return (Wait, "no candles".to_string());
         }
 
         let candles = option_candles.unwrap();
 
-        if candles.is_empty() {
+        if candles.len() < 2 {
             return (Wait, "no candles".to_string());
         }
 
-        let stochastic = if let Some(val) = sc.get_stochastic(timeframe, symbol) {
+        let p = &self.params;
+        let stochastic = if let Some(val) = sc.get_stochastic(timeframe, symbol, p.period_k, p.smooth_k, p.period_d) {
             val
         } else { return (Wait, "No stochastic".to_string()) };
 
-
-        let ema200 = if let Some(val) = sc.get_ema(timeframe, symbol, 200) {
+        let ema = if let Some(val) = sc.get_ema(timeframe, symbol, p.trend_ema) {
             val[val.len() - 1]
         } else { return (Wait, "No Ema".to_string()) };
 
         let closes = get_close_prices(candles);
 
-
         let n = stochastic.d.len();
         let stoch_current= stochastic.d[n-1];
         let stoch_prev = stochastic.d[n - 2];
@@ -191,54 +355,16 @@ impl Strategy for StocBorder {
 
         let info = format!(
             "p:{:.2}, stoc{:.2}, stoc_prev:{:.2}, em:{:.2}",
-            price, stoch_current, stoch_prev, ema200
+            price, stoch_current, stoch_prev, ema
         );
 
 
-        if stoch_prev < 20.0 && stoch_current > 20.0 && price > ema200 {
+        if stoch_prev < p.oversold && stoch_current > p.oversold && price > ema {
             (Long, info)
-        } else if stoch_prev > 80.0 && stoch_current < 80.0 && price < ema200 {
+        } else if stoch_prev > p.overbought && stoch_current < p.overbought && price < ema {
             (Short, info)
         } else {
             (Wait, info)
         }
     }
 }
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
@@ -1,29 +1,79 @@
 use crate::enums::{OrderCommand, Symbol, Timeframe};
+use crate::indicators::IndicatorSpec;
 use crate::models::models::StrategyContainer;
 use crate::strategy::str_impl::{EmaBounce, EmaMacd, EmaMacd2, StocBorder};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::sync::Arc;
 
+/// Longest indicator period a strategy may ask for, the EntryManager fetches 202 closed candles.
+pub const MAX_PERIOD: usize = 200;
+
 pub trait Strategy {
     #[allow(dead_code)]
     fn name(&self) -> &str;
+    /// The parameters the instance runs with, defaults filled in.
+    fn params(&self) -> Value;
+    /// Indicators `run` reads from the `StrategyContainer`.
+    fn indicators(&self) -> Vec<IndicatorSpec>;
     fn run(&self, sc: &StrategyContainer, timeframe: &Timeframe, symbol: &Symbol) -> (OrderCommand, String);
 }
 
-pub fn get_strategy(name: &str) -> Arc<dyn Strategy + Send + Sync> {
+/// Typed settings of a strategy, missing fields take their default.
+pub trait StrategyParams: DeserializeOwned + Default {
+    fn validate(&self) -> Result<(), String>;
+}
+
+/// Every strategy `find_strategy` knows.
+pub const STRATEGIES: [&str; 4] = ["EmaMacd", "EmaMacd2", "EmaBounce", "StocBorder"];
+
+/// Builds a strategy from its name and a JSON params object, `null` means all defaults.
+pub fn find_strategy(name: &str, params: &Value) -> Result<Arc<dyn Strategy + Send + Sync>, String> {
     match name.to_lowercase().as_str() {
-        "emamacd" => Arc::new(EmaMacd {}),
-        "emamacd2" => Arc::new(EmaMacd2 {}),
-        "emabounce" => Arc::new(EmaBounce {}),
-        "stocborder" => Arc::new(StocBorder {}),
-        _ => Arc::new(DummyStrategy {}),
+        "emamacd" => Ok(Arc::new(EmaMacd::new(parse_params(params)?))),
+        "emamacd2" => Ok(Arc::new(EmaMacd2::new(parse_params(params)?))),
+        "emabounce" => Ok(Arc::new(EmaBounce::new(parse_params(params)?))),
+        "stocborder" => Ok(Arc::new(StocBorder::new(parse_params(params)?))),
+        _ => Err(format!("unknown strategy: {}", name)),
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct DummyStrategy;
-impl Strategy for DummyStrategy {
-    fn name(&self) -> &str { "dummy" }
-    fn run(&self, _sc: &StrategyContainer, _timeframe: &Timeframe, _symbol: &Symbol) -> (OrderCommand, String) {
-        (OrderCommand::Long, "dummy strategy".to_string())
+fn parse_params<P: StrategyParams>(params: &Value) -> Result<P, String> {
+    let params: P = match params {
+        Value::Null => P::default(),
+        _ => serde_json::from_value(params.clone()).map_err(|e| format!("invalid params: {}", e))?,
+    };
+    params.validate()?;
+    Ok(params)
+}
+
+pub fn check_period(name: &str, period: usize) -> Result<(), String> {
+    if !(1..=MAX_PERIOD).contains(&period) {
+        return Err(format!("{} must be between 1 and {}", name, MAX_PERIOD));
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn params_default_and_validate() {
+        let defaults = find_strategy("StocBorder", &Value::Null).unwrap().params();
+        assert_eq!(defaults["oversold"], json!(20.0));
+        assert_eq!(defaults["trend_ema"], json!(200));
+
+        let tuned = find_strategy("stocborder", &json!({"oversold": 10.0, "overbought": 90.0})).unwrap().params();
+        assert_eq!(tuned["oversold"], json!(10.0));
+        assert_eq!(tuned["period_k"], defaults["period_k"]);
+
+        assert!(find_strategy("StocBorder", &json!({"oversold": 90.0, "overbought": 10.0})).is_err());
+        assert!(find_strategy("EmaBounce", &json!({"fast_ema": 200, "slow_ema": 50})).is_err());
+        assert!(find_strategy("EmaMacd", &json!({"trend_ema": 500})).is_err());
+        assert!(find_strategy("EmaMacd", &json!({"macd_fast": 26, "macd_slow": 12})).is_err());
+        assert!(find_strategy("EmaMacd", &json!({"trend": 100})).is_err());
+        assert!(find_strategy("Nope", &Value::Null).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use crate::config;
+use crate::tools;
+use chrono::{DateTime, FixedOffset};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    /// Failed and waiting out the backoff before the next start.
+    Restarting,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: String,
+    pub status: TaskStatus,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub started_at: DateTime<FixedOffset>,
+}
+
+/// Runs the long-lived loops, restarts them with exponential backoff when they panic or return,
+/// and stops them together on shutdown.
+///
+/// Tasks get a child of the shutdown token and are expected to return once it is cancelled,
+/// finishing the tick they are in so no half-written state is left behind.
+#[derive(Debug)]
+pub struct Supervisor {
+    token: CancellationToken,
+    health: Arc<Mutex<BTreeMap<String, TaskHealth>>>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::with_backoff(INITIAL_BACKOFF, MAX_BACKOFF)
+    }
+
+    /// A task that ran for at least `max_backoff` before failing starts again from `initial_backoff`.
+    pub fn with_backoff(initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            health: Arc::new(Mutex::new(BTreeMap::new())),
+            handles: Mutex::new(Vec::new()),
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// Starts `make(token)` now and again after every failure until shutdown.
+    pub fn spawn<F, Fut>(&self, name: &str, mut make: F)
+    where
+        F: FnMut(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.to_string();
+        let token = self.token.clone();
+        let health = Arc::clone(&self.health);
+        let (initial_backoff, max_backoff) = (self.initial_backoff, self.max_backoff);
+
+        let handle = tokio::spawn(async move {
+            let mut backoff = initial_backoff;
+            let mut restarts = 0;
+            let mut last_error = None;
+
+            loop {
+                set_health(&health, &name, TaskStatus::Running, restarts, &last_error);
+                let started = Instant::now();
+                let result = tokio::spawn(make(token.child_token())).await;
+
+                if token.is_cancelled() {
+                    if let Err(e) = result {
+                        warn!("{} failed while stopping: {}", name, describe(e));
+                    }
+                    break;
+                }
+
+                let reason = match result {
+                    Ok(()) => "returned unexpectedly".to_string(),
+                    Err(e) => describe(e),
+                };
+                if started.elapsed() >= max_backoff {
+                    backoff = initial_backoff;
+                }
+                error!("{} stopped: {}, restarting in {:?}", name, reason, backoff);
+                restarts += 1;
+                last_error = Some(reason);
+                set_health(&health, &name, TaskStatus::Restarting, restarts, &last_error);
+
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(max_backoff);
+            }
+
+            set_health(&health, &name, TaskStatus::Stopped, restarts, &last_error);
+        });
+
+        self.handles.lock().unwrap().push(handle);
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.health.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health.lock().unwrap().values().all(|t| t.status == TaskStatus::Running)
+    }
+
+    /// Cancels every task and waits up to `timeout` for them to finish their current tick.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.token.cancel();
+        let handles: Vec<JoinHandle<()>> = self.handles.lock().unwrap().drain(..).collect();
+        let count = handles.len();
+
+        let wait = async {
+            for handle in handles {
+                let _ = handle.await;
+            }
+        };
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(()) => info!("stopped {} tasks", count),
+            Err(_) => warn!("tasks still running after {:?}, not waiting any longer", timeout),
+        }
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn set_health(health: &Mutex<BTreeMap<String, TaskHealth>>, name: &str, status: TaskStatus, restarts: u32, last_error: &Option<String>) {
+    let mut health = health.lock().unwrap();
+    let started_at = match health.get(name) {
+        Some(t) if status != TaskStatus::Running => t.started_at,
+        _ => tools::get_date(config::get().time_zone),
+    };
+    health.insert(name.to_string(), TaskHealth {
+        name: name.to_string(),
+        status,
+        restarts,
+        last_error: last_error.clone(),
+        started_at,
+    });
+}
+
+fn describe(e: JoinError) -> String {
+    if !e.is_panic() {
+        return e.to_string();
+    }
+    let payload = e.into_panic();
+    match payload.downcast_ref::<&str>() {
+        Some(s) => format!("panicked: {}", s),
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => format!("panicked: {}", s),
+            None => "panicked".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    async fn wait_for(supervisor: &Supervisor, check: impl Fn(&TaskHealth) -> bool) -> TaskHealth {
+        for _ in 0..200 {
+            if let Some(task) = supervisor.health().into_iter().find(|t| check(t)) {
+                return task;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("task never reached the expected state: {:?}", supervisor.health());
+    }
+
+    #[tokio::test]
+    async fn failed_tasks_are_restarted() {
+        let supervisor = Supervisor::with_backoff(Duration::from_millis(1), Duration::from_millis(4));
+        let starts = Arc::new(AtomicU32::new(0));
+
+        let counter = Arc::clone(&starts);
+        supervisor.spawn("flaky", move |token| {
+            let start = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if start < 2 {
+                    panic!("start {}", start);
+                }
+                token.cancelled().await;
+            }
+        });
+
+        let task = wait_for(&supervisor, |t| t.restarts == 2 && t.status == TaskStatus::Running).await;
+        assert_eq!(task.last_error.as_deref(), Some("panicked: start 1"));
+        assert!(supervisor.is_healthy());
+
+        supervisor.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(supervisor.health()[0].status, TaskStatus::Stopped);
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn a_task_in_backoff_is_unhealthy() {
+        let supervisor = Supervisor::with_backoff(Duration::from_secs(60), Duration::from_secs(60));
+        supervisor.spawn("returns", |_| async {});
+
+        let task = wait_for(&supervisor, |t| t.status == TaskStatus::Restarting).await;
+        assert_eq!(task.last_error.as_deref(), Some("returned unexpectedly"));
+        assert!(!supervisor.is_healthy());
+
+        // the backoff is cut short by the shutdown
+        supervisor.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(supervisor.health()[0].status, TaskStatus::Stopped);
+    }
+}
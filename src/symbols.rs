@@ -0,0 +1,230 @@
+use crate::config::Config;
+use crate::connector::{value_to_f64, BinanceConnector};
+use crate::enums::Symbol;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+/// Exchange trading rules of a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolInfo {
+    #[serde(deserialize_with = "Symbol::deserialize_trusted")]
+    pub symbol: Symbol,
+    /// Price increment, `PRICE_FILTER.tickSize`.
+    pub tick_size: f64,
+    /// Quantity increment, `LOT_SIZE.stepSize`.
+    pub step_size: f64,
+    /// Smallest order value in quote asset, `MIN_NOTIONAL.notional`.
+    pub min_notional: f64,
+}
+
+impl SymbolInfo {
+    /// Nearest valid price, PRICE_FILTER.
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_to_step(price, self.tick_size, f64::round)
+    }
+
+    /// Largest valid quantity not above `quantity`, LOT_SIZE.
+    pub fn round_quantity(&self, quantity: f64) -> f64 {
+        round_to_step(quantity, self.step_size, f64::floor)
+    }
+
+    /// Price as sent to the exchange, rounded and with the tick size's decimals.
+    pub fn format_price(&self, price: f64) -> String {
+        format!("{:.*}", step_decimals(self.tick_size) as usize, self.round_price(price))
+    }
+
+    /// Quantity as sent to the exchange, rounded down and with the step size's decimals.
+    pub fn format_quantity(&self, quantity: f64) -> String {
+        format!("{:.*}", step_decimals(self.step_size) as usize, self.round_quantity(quantity))
+    }
+
+    /// MIN_NOTIONAL, the exchange rejects orders worth less.
+    pub fn check_notional(&self, price: f64, quantity: f64) -> Result<(), String> {
+        let notional = price * quantity;
+        if quantity <= 0.0 || notional < self.min_notional {
+            return Err(format!(
+                "{} order of {} at {} is worth {:.4}, below min notional {}",
+                self.symbol, quantity, price, notional, self.min_notional
+            ));
+        }
+        Ok(())
+    }
+}
+
+// `value / step` can land a hair under an integer, the small epsilon keeps 0.3 / 0.1 at 3 steps
+fn round_to_step(value: f64, step: f64, round: fn(f64) -> f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    let ratio = value / step;
+    let steps = round(ratio + ratio.abs() * 1e-12 + 1e-9);
+    let decimals = step_decimals(step);
+    let factor = 10f64.powi(decimals);
+    (steps * step * factor).round() / factor
+}
+
+/// For symbols without known rules, at most 8 decimals and no trailing zeros, never `0.30000000000000004`.
+pub fn format_decimal(value: f64) -> String {
+    let s = format!("{:.8}", value);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn step_decimals(step: f64) -> i32 {
+    let mut decimals = 0;
+    let mut scaled = step;
+    while decimals < 12 && (scaled - scaled.round()).abs() > 1e-9 {
+        scaled *= 10.0;
+        decimals += 1;
+    }
+    decimals
+}
+
+static SYMBOLS: OnceLock<RwLock<HashMap<Symbol, SymbolInfo>>> = OnceLock::new();
+
+fn symbols() -> &'static RwLock<HashMap<Symbol, SymbolInfo>> {
+    SYMBOLS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Adds or replaces the rules of `info.symbol`.
+pub fn register(info: SymbolInfo) {
+    symbols().write().unwrap().insert(info.symbol, info);
+}
+
+/// Adds rules only for symbols that aren't known yet, so configured values win over exchange data.
+pub fn register_missing(infos: impl IntoIterator<Item = SymbolInfo>) {
+    let mut symbols = symbols().write().unwrap();
+    for info in infos {
+        symbols.entry(info.symbol).or_insert(info);
+    }
+}
+
+pub fn get(symbol: &Symbol) -> Option<SymbolInfo> {
+    symbols().read().unwrap().get(symbol).copied()
+}
+
+pub fn all() -> Vec<SymbolInfo> {
+    let mut infos: Vec<SymbolInfo> = symbols().read().unwrap().values().copied().collect();
+    infos.sort_by_key(|i| i.symbol);
+    infos
+}
+
+pub fn load_exchange_info_file(path: &Path) -> Result<Vec<SymbolInfo>, Box<dyn Error + Send + Sync>> {
+    let body: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(parse_exchange_info(&body))
+}
+
+/// Registers the configured rules, then the rest from `symbols_file` or the exchange.
+pub async fn load(config: &Config) {
+    for info in config.symbols.iter() {
+        register(*info);
+    }
+
+    let infos = match config.symbols_file.as_ref() {
+        Some(path) => load_exchange_info_file(Path::new(path)),
+        None => BinanceConnector::new().get_exchange_info().await,
+    };
+    match infos {
+        Ok(infos) => register_missing(infos),
+        Err(e) => warn!("failed to load exchange info, only configured symbol rules are known: {}", e),
+    }
+}
+
+/// Reads the `symbols` array of a `/fapi/v1/exchangeInfo` response, skipping symbols not trading.
+pub fn parse_exchange_info(body: &Value) -> Vec<SymbolInfo> {
+    let Some(entries) = body["symbols"].as_array() else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter(|s| s["status"].as_str() == Some("TRADING"))
+        .filter_map(|s| {
+            let symbol = Symbol::intern(s["symbol"].as_str()?).ok()?;
+            let filters = s["filters"].as_array()?;
+            let filter = |kind: &str, key: &str| {
+                filters
+                    .iter()
+                    .find(|f| f["filterType"].as_str() == Some(kind))
+                    .and_then(|f| value_to_f64(&f[key]).ok())
+            };
+
+            Some(SymbolInfo {
+                symbol,
+                tick_size: filter("PRICE_FILTER", "tickSize")?,
+                step_size: filter("LOT_SIZE", "stepSize")?,
+                min_notional: filter("MIN_NOTIONAL", "notional").unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_exchange_info_filters() {
+        let body = json!({"symbols": [
+            {"symbol": "XRPUSDT", "status": "TRADING", "filters": [
+                {"filterType": "PRICE_FILTER", "tickSize": "0.0001"},
+                {"filterType": "LOT_SIZE", "stepSize": "0.1"},
+                {"filterType": "MIN_NOTIONAL", "notional": "5"}
+            ]},
+            {"symbol": "OLDUSDT", "status": "SETTLING", "filters": []}
+        ]});
+
+        let infos = parse_exchange_info(&body);
+        assert_eq!(infos, vec![SymbolInfo { symbol: Symbol::new("XRPUSDT"), tick_size: 0.0001, step_size: 0.1, min_notional: 5.0 }]);
+    }
+
+    #[test]
+    fn rounds_to_exchange_increments() {
+        let info = SymbolInfo { symbol: Symbol::new("SOLUSDT"), tick_size: 0.01, step_size: 0.01, min_notional: 5.0 };
+        assert_eq!(info.round_price(143.456), 143.46);
+        assert_eq!(info.round_price(143.454), 143.45);
+        assert_eq!(info.round_quantity(6.9999), 6.99);
+        assert_eq!(info.round_quantity(0.3), 0.3);
+
+        let btc = SymbolInfo { symbol: Symbol::new("BTCUSDT"), tick_size: 0.1, step_size: 0.001, min_notional: 100.0 };
+        assert_eq!(btc.round_price(97123.26), 97123.3);
+        assert_eq!(btc.round_quantity(0.0104), 0.01);
+        assert!(btc.check_notional(97123.3, 0.001).is_err());
+        assert!(btc.check_notional(97123.3, 0.002).is_ok());
+        assert!(btc.check_notional(97123.3, 0.0).is_err());
+
+        assert_eq!(info.format_quantity(0.1 + 0.2), "0.30");
+        assert_eq!(btc.format_price(97123.26), "97123.3");
+        assert_eq!(btc.format_quantity(0.0104), "0.010");
+        assert_eq!(format_decimal(0.1 + 0.2), "0.3");
+        assert_eq!(format_decimal(25.0), "25");
+    }
+
+    #[test]
+    fn symbols_are_data() {
+        let sol = Symbol::new("SOLUSDT");
+        let legacy: Symbol = "SolUsdt".parse().unwrap();
+        assert_eq!(legacy, sol);
+        assert_eq!(legacy.to_string(), "SOLUSDT");
+        assert_eq!(Symbol::new("1000pepeusdt").base_asset(), "1000PEPE");
+        assert_eq!(serde_json::to_string(&legacy).unwrap(), "\"SOLUSDT\"");
+        assert!("SOL/USDT".parse::<Symbol>().is_err());
+
+        // untrusted input only finds known names, it never interns new ones
+        assert_eq!("NeverListedUsdt".parse::<Symbol>().unwrap_err(), "unknown symbol: NEVERLISTEDUSDT");
+        assert!(serde_json::from_str::<Symbol>("\"NEVERLISTEDUSDT\"").is_err());
+        assert!(serde_json::from_str::<SymbolInfo>(r#"{"symbol": "NEWLISTINGUSDT", "tick_size": 0.1, "step_size": 1, "min_notional": 5}"#).is_ok());
+        assert_eq!("newlistingusdt".parse::<Symbol>().unwrap().as_str(), "NEWLISTINGUSDT");
+
+        register(SymbolInfo { symbol: Symbol::new("DOGEUSDT"), tick_size: 0.00001, step_size: 1.0, min_notional: 5.0 });
+        register_missing([SymbolInfo { symbol: Symbol::new("DOGEUSDT"), tick_size: 1.0, step_size: 1.0, min_notional: 1.0 }]);
+        assert_eq!(get(&Symbol::new("dogeusdt")).unwrap().tick_size, 0.00001);
+    }
+}
@@ -1,4 +1,4 @@
-
+use std::collections::VecDeque;
 
 #[allow(dead_code)]
 pub fn bollinger_bands_b(prices: &[f64], period: usize) -> f64 {
@@ -42,8 +42,8 @@ pub fn slma(prices: &[f64], period: usize) -> f64 {
 }
 
 #[allow(dead_code)]
-pub fn macd(prices: &[f64]) -> (f64, f64, f64) {
-    let (macd, signal, hist) = macd_slice(prices);
+pub fn macd(prices: &[f64], fast: usize, slow: usize, signal: usize) -> (f64, f64, f64) {
+    let (macd, signal, hist) = macd_slice(prices, fast, slow, signal);
     (
         *macd.last().unwrap_or(&0.0),
         *signal.last().unwrap_or(&0.0),
@@ -51,11 +51,17 @@ pub fn macd(prices: &[f64]) -> (f64, f64, f64) {
     )
 }
 
-pub fn macd_slice(prices: &[f64]) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    let fast = ema_slice(prices, 12);
-    let slow = ema_slice(prices, 25);
-    let macd: Vec<f64> = fast.iter().zip(slow.iter()).map(|(f, s)| f - s).collect();
-    let signal = ema_slice(&macd, 9);
+/// Returns `(macd, signal, histogram)`, the line starts at `slow - 1` and the signal `signal - 1` bars later.
+///
+/// Like TA-Lib the fast EMA is seeded on the bars just before the slow one's first value,
+/// so both start on the same bar.
+pub fn macd_slice(prices: &[f64], fast: usize, slow: usize, signal: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let delay = slow.saturating_sub(fast).min(prices.len());
+    let mut fast_line = vec![f64::NAN; delay];
+    fast_line.extend(ema_slice(&prices[delay..], fast));
+    let slow_line = ema_slice(prices, slow);
+    let macd: Vec<f64> = fast_line.iter().zip(slow_line.iter()).map(|(f, s)| f - s).collect();
+    let signal = ema_slice(&macd, signal);
     let histogram: Vec<f64> = macd.iter().zip(signal.iter()).map(|(m, s)| m - s).collect();
     (macd, signal, histogram)
 }
@@ -76,15 +82,19 @@ pub fn slma_slice(prices: &[f64], period: usize) -> Vec<f64> {
         .collect()
 }
 
+/// EMA seeded with the SMA of the first `period` prices, NaN before that.
+///
+/// Leading NaNs, the warm-up of the series it smooths, are skipped.
 pub fn ema_slice(prices: &[f64], period: usize) -> Vec<f64> {
-    let mut result = vec![0.0; prices.len()];
-    if prices.is_empty() || period == 0 {
+    let mut result = vec![f64::NAN; prices.len()];
+    let start = prices.iter().position(|p| !p.is_nan()).unwrap_or(prices.len());
+    if period == 0 || prices.len() - start < period {
         return result;
     }
     let k = 2.0 / (period + 1) as f64;
-    let initial_ema = prices.iter().take(period).sum::<f64>() / period as f64;
-    result[period - 1] = initial_ema;
-    for i in period..prices.len() {
+    let seed = start + period - 1;
+    result[seed] = prices[start..=seed].iter().sum::<f64>() / period as f64;
+    for i in seed + 1..prices.len() {
         result[i] = (prices[i] - result[i - 1]) * k + result[i - 1];
     }
     result
@@ -155,14 +165,16 @@ pub fn stochastic_slice(
     (k, d)
 }
 
+/// SMA skipping non-finite values, leading NaNs are warm-up and don't count toward the first window.
 pub fn sma_slice(values: &[f64], period: usize) -> Vec<f64> {
     let mut result = vec![f64::NAN; values.len()];
+    let start = values.iter().position(|v| !v.is_nan()).unwrap_or(values.len());
 
-    if period == 0 || values.len() < period {
+    if period == 0 || values.len() - start < period {
         return result;
     }
 
-    for i in period - 1..values.len() {
+    for i in start + period - 1..values.len() {
         let mut sum = 0.0;
         let mut count = 0;
 
@@ -179,4 +191,746 @@ pub fn sma_slice(values: &[f64], period: usize) -> Vec<f64> {
     }
 
     result
-}
\ No newline at end of file
+}
+
+fn highest(values: &[f64], end: usize, period: usize) -> f64 {
+    values[end + 1 - period..=end].iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b))
+}
+
+fn lowest(values: &[f64], end: usize, period: usize) -> f64 {
+    values[end + 1 - period..=end].iter().fold(f64::INFINITY, |a, &b| a.min(b))
+}
+
+/// True range, the first bar has no previous close and uses its high - low.
+pub fn true_range_slice(high: &[f64], low: &[f64], close: &[f64]) -> Vec<f64> {
+    (0..close.len())
+        .map(|i| {
+            let range = high[i] - low[i];
+            if i == 0 {
+                return range;
+            }
+            range.max((high[i] - close[i - 1]).abs()).max((low[i] - close[i - 1]).abs())
+        })
+        .collect()
+}
+
+/// Wilder's RSI, the first value is at `period`.
+pub fn rsi_slice(close: &[f64], period: usize) -> Vec<f64> {
+    let n = close.len();
+    let mut result = vec![f64::NAN; n];
+    if period == 0 || n <= period {
+        return result;
+    }
+
+    let rsi = |gain: f64, loss: f64| if gain + loss == 0.0 { 0.0 } else { 100.0 * gain / (gain + loss) };
+    let (mut gain, mut loss) = (0.0, 0.0);
+    for i in 1..=period {
+        let change = close[i] - close[i - 1];
+        if change > 0.0 { gain += change } else { loss -= change }
+    }
+    gain /= period as f64;
+    loss /= period as f64;
+    result[period] = rsi(gain, loss);
+
+    let p = period as f64;
+    for i in period + 1..n {
+        let change = close[i] - close[i - 1];
+        gain = (gain * (p - 1.0) + change.max(0.0)) / p;
+        loss = (loss * (p - 1.0) + (-change).max(0.0)) / p;
+        result[i] = rsi(gain, loss);
+    }
+    result
+}
+
+/// Wilder's ATR, the first value is at `period`, the mean of the true ranges since bar 1.
+pub fn atr_slice(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    let n = close.len();
+    let mut result = vec![f64::NAN; n];
+    if period == 0 || n <= period {
+        return result;
+    }
+
+    let tr = true_range_slice(high, low, close);
+    let p = period as f64;
+    let mut atr = tr[1..=period].iter().sum::<f64>() / p;
+    result[period] = atr;
+    for i in period + 1..n {
+        atr = (atr * (p - 1.0) + tr[i]) / p;
+        result[i] = atr;
+    }
+    result
+}
+
+/// Returns `(adx, plus_di, minus_di)`, the DIs start at `period` and the ADX at `2 * period - 1`.
+pub fn adx_slice(high: &[f64], low: &[f64], close: &[f64], period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let n = close.len();
+    let mut adx = vec![f64::NAN; n];
+    let mut plus_di = vec![f64::NAN; n];
+    let mut minus_di = vec![f64::NAN; n];
+    if period == 0 || n <= period {
+        return (adx, plus_di, minus_di);
+    }
+
+    let tr = true_range_slice(high, low, close);
+    let dm = |i: usize| {
+        let up = high[i] - high[i - 1];
+        let down = low[i - 1] - low[i];
+        let plus = if up > down && up > 0.0 { up } else { 0.0 };
+        let minus = if down > up && down > 0.0 { down } else { 0.0 };
+        (plus, minus)
+    };
+
+    // Wilder sums, seeded with the first period - 1 bars
+    let p = period as f64;
+    let (mut plus_sum, mut minus_sum, mut tr_sum) = (0.0, 0.0, 0.0);
+    for (i, tr) in tr.iter().enumerate().take(period).skip(1) {
+        let (plus, minus) = dm(i);
+        plus_sum += plus;
+        minus_sum += minus;
+        tr_sum += tr;
+    }
+
+    let mut dx = vec![f64::NAN; n];
+    for i in period..n {
+        let (plus, minus) = dm(i);
+        plus_sum = plus_sum - plus_sum / p + plus;
+        minus_sum = minus_sum - minus_sum / p + minus;
+        tr_sum = tr_sum - tr_sum / p + tr[i];
+
+        let (pdi, mdi) = if tr_sum == 0.0 { (0.0, 0.0) } else { (100.0 * plus_sum / tr_sum, 100.0 * minus_sum / tr_sum) };
+        plus_di[i] = pdi;
+        minus_di[i] = mdi;
+        dx[i] = if pdi + mdi == 0.0 { 0.0 } else { 100.0 * (pdi - mdi).abs() / (pdi + mdi) };
+    }
+
+    let first = 2 * period - 1;
+    if n > first {
+        let mut value = dx[period..=first].iter().sum::<f64>() / p;
+        adx[first] = value;
+        for i in first + 1..n {
+            value = (value * (p - 1.0) + dx[i]) / p;
+            adx[i] = value;
+        }
+    }
+    (adx, plus_di, minus_di)
+}
+
+/// Returns `(line, direction)`, direction is 1 in an up trend, where the line is the lower band,
+/// and -1 in a down trend.
+pub fn supertrend_slice(high: &[f64], low: &[f64], close: &[f64], period: usize, multiplier: f64) -> (Vec<f64>, Vec<f64>) {
+    let n = close.len();
+    let mut line = vec![f64::NAN; n];
+    let mut direction = vec![f64::NAN; n];
+    let atr = atr_slice(high, low, close, period);
+
+    let mut prev: Option<(f64, f64, f64)> = None; // (upper, lower, direction)
+    for i in 0..n {
+        if atr[i].is_nan() {
+            continue;
+        }
+        let mid = (high[i] + low[i]) / 2.0;
+        let basic_upper = mid + multiplier * atr[i];
+        let basic_lower = mid - multiplier * atr[i];
+
+        let (upper, lower, dir) = match prev {
+            None => (basic_upper, basic_lower, 1.0),
+            Some((prev_upper, prev_lower, prev_dir)) => {
+                let upper = if basic_upper < prev_upper || close[i - 1] > prev_upper { basic_upper } else { prev_upper };
+                let lower = if basic_lower > prev_lower || close[i - 1] < prev_lower { basic_lower } else { prev_lower };
+                let dir = if close[i] > prev_upper {
+                    1.0
+                } else if close[i] < prev_lower {
+                    -1.0
+                } else {
+                    prev_dir
+                };
+                (upper, lower, dir)
+            }
+        };
+
+        line[i] = if dir > 0.0 { lower } else { upper };
+        direction[i] = dir;
+        prev = Some((upper, lower, dir));
+    }
+    (line, direction)
+}
+
+/// Volume weighted typical price over the last `period` bars.
+pub fn vwap_slice(high: &[f64], low: &[f64], close: &[f64], volume: &[f64], period: usize) -> Vec<f64> {
+    let n = close.len();
+    let mut result = vec![f64::NAN; n];
+    if period == 0 || n < period {
+        return result;
+    }
+
+    let typical: Vec<f64> = (0..n).map(|i| (high[i] + low[i] + close[i]) / 3.0).collect();
+    for (i, out) in result.iter_mut().enumerate().skip(period - 1) {
+        let window = i + 1 - period..=i;
+        let value: f64 = typical[window.clone()].iter().zip(&volume[window.clone()]).map(|(t, v)| t * v).sum();
+        let total: f64 = volume[window].iter().sum();
+        if total > 0.0 {
+            *out = value / total;
+        }
+    }
+    result
+}
+
+/// On-balance volume, starting from the first bar's volume.
+pub fn obv_slice(close: &[f64], volume: &[f64]) -> Vec<f64> {
+    let mut result = Vec::with_capacity(close.len());
+    for i in 0..close.len() {
+        let obv = match i {
+            0 => volume[0],
+            _ if close[i] > close[i - 1] => result[i - 1] + volume[i],
+            _ if close[i] < close[i - 1] => result[i - 1] - volume[i],
+            _ => result[i - 1],
+        };
+        result.push(obv);
+    }
+    result
+}
+
+/// Returns `(upper, middle, lower)`, an EMA of the closes with ATR bands.
+pub fn keltner_slice(high: &[f64], low: &[f64], close: &[f64], period: usize, atr_period: usize, multiplier: f64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let middle = ema_slice(close, period);
+    let atr = atr_slice(high, low, close, atr_period);
+    let upper = middle.iter().zip(&atr).map(|(m, a)| m + multiplier * a).collect();
+    let lower = middle.iter().zip(&atr).map(|(m, a)| m - multiplier * a).collect();
+    (upper, middle, lower)
+}
+
+/// Returns `(upper, middle, lower)`, the highest high, the midpoint and the lowest low of `period` bars.
+pub fn donchian_slice(high: &[f64], low: &[f64], period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let n = high.len();
+    let mut upper = vec![f64::NAN; n];
+    let mut middle = vec![f64::NAN; n];
+    let mut lower = vec![f64::NAN; n];
+    if period == 0 || n < period {
+        return (upper, middle, lower);
+    }
+
+    for i in period - 1..n {
+        upper[i] = highest(high, i, period);
+        lower[i] = lowest(low, i, period);
+        middle[i] = (upper[i] + lower[i]) / 2.0;
+    }
+    (upper, middle, lower)
+}
+
+/// Returns `[conversion, base, span_a, span_b, lagging]`.
+///
+/// The spans are shifted forward by `displacement`, so index `i` holds the cloud drawn at bar `i`.
+/// The lagging span is the close `displacement` bars later, NaN for the last `displacement` bars.
+pub fn ichimoku_slice(
+    high: &[f64],
+    low: &[f64],
+    close: &[f64],
+    conversion: usize,
+    base: usize,
+    span_b: usize,
+    displacement: usize,
+) -> [Vec<f64>; 5] {
+    let n = close.len();
+    let (_, conversion_line, _) = donchian_slice(high, low, conversion);
+    let (_, base_line, _) = donchian_slice(high, low, base);
+    let (_, span_b_mid, _) = donchian_slice(high, low, span_b);
+
+    let mut span_a = vec![f64::NAN; n];
+    let mut span_b_line = vec![f64::NAN; n];
+    let mut lagging = vec![f64::NAN; n];
+    for i in displacement..n {
+        span_a[i] = (conversion_line[i - displacement] + base_line[i - displacement]) / 2.0;
+        span_b_line[i] = span_b_mid[i - displacement];
+    }
+    if n > displacement {
+        lagging[..n - displacement].copy_from_slice(&close[displacement..]);
+    }
+    [conversion_line, base_line, span_a, span_b_line, lagging]
+}
+
+/// Wilder's parabolic SAR, the first value is at bar 1.
+///
+/// The first trend is short when bar 1 has a larger down move than up move, long otherwise.
+pub fn psar_slice(high: &[f64], low: &[f64], step: f64, max: f64) -> Vec<f64> {
+    let n = high.len();
+    let mut result = vec![f64::NAN; n];
+    if n < 2 {
+        return result;
+    }
+
+    let down = low[0] - low[1];
+    let mut is_long = !(down > 0.0 && down > high[1] - high[0]);
+    let mut af = step.min(max);
+    let (mut ep, mut sar) = if is_long { (high[1], low[0]) } else { (low[1], high[0]) };
+    let (mut new_high, mut new_low) = (high[1], low[1]);
+
+    for i in 1..n {
+        let (prev_high, prev_low) = (new_high, new_low);
+        new_high = high[i];
+        new_low = low[i];
+
+        if is_long {
+            if new_low <= sar {
+                // reversal, the SAR jumps to the extreme point of the finished trend
+                is_long = false;
+                sar = ep.max(prev_high).max(new_high);
+                result[i] = sar;
+                af = step.min(max);
+                ep = new_low;
+                sar = (sar + af * (ep - sar)).max(prev_high).max(new_high);
+            } else {
+                result[i] = sar;
+                if new_high > ep {
+                    ep = new_high;
+                    af = (af + step).min(max);
+                }
+                sar = (sar + af * (ep - sar)).min(prev_low).min(new_low);
+            }
+        } else if new_high >= sar {
+            is_long = true;
+            sar = ep.min(prev_low).min(new_low);
+            result[i] = sar;
+            af = step.min(max);
+            ep = new_high;
+            sar = (sar + af * (ep - sar)).min(prev_low).min(new_low);
+        } else {
+            result[i] = sar;
+            if new_low < ep {
+                ep = new_low;
+                af = (af + step).min(max);
+            }
+            sar = (sar + af * (ep - sar)).max(prev_high).max(new_high);
+        }
+    }
+    result
+}
+
+/// Commodity channel index, 0 when the window has no deviation.
+pub fn cci_slice(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    let n = close.len();
+    let mut result = vec![f64::NAN; n];
+    if period == 0 || n < period {
+        return result;
+    }
+
+    let typical: Vec<f64> = (0..n).map(|i| (high[i] + low[i] + close[i]) / 3.0).collect();
+    for i in period - 1..n {
+        let window = &typical[i + 1 - period..=i];
+        let mean = window.iter().sum::<f64>() / period as f64;
+        let deviation = window.iter().map(|t| (t - mean).abs()).sum::<f64>() / period as f64;
+        result[i] = if deviation == 0.0 { 0.0 } else { (typical[i] - mean) / (0.015 * deviation) };
+    }
+    result
+}
+
+/// Williams %R between -100 and 0, 0 when the window has no range.
+pub fn williams_r_slice(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    let n = close.len();
+    let mut result = vec![f64::NAN; n];
+    if period == 0 || n < period {
+        return result;
+    }
+
+    for i in period - 1..n {
+        let (hh, ll) = (highest(high, i, period), lowest(low, i, period));
+        result[i] = if hh == ll { 0.0 } else { -100.0 * (hh - close[i]) / (hh - ll) };
+    }
+    result
+}
+
+/// Returns Heikin-Ashi `(open, high, low, close)`.
+pub fn heikin_ashi_slice(open: &[f64], high: &[f64], low: &[f64], close: &[f64]) -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>) {
+    let n = close.len();
+    let mut ha_open = Vec::with_capacity(n);
+    let mut ha_high = Vec::with_capacity(n);
+    let mut ha_low = Vec::with_capacity(n);
+    let mut ha_close = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let c = (open[i] + high[i] + low[i] + close[i]) / 4.0;
+        let o = if i == 0 { (open[0] + close[0]) / 2.0 } else { (ha_open[i - 1] + ha_close[i - 1]) / 2.0 };
+        ha_high.push(high[i].max(o).max(c));
+        ha_low.push(low[i].min(o).min(c));
+        ha_open.push(o);
+        ha_close.push(c);
+    }
+    (ha_open, ha_high, ha_low, ha_close)
+}
+
+/// Streaming EMA, `next` returns what `ema_slice` gives for the last of all prices fed so far.
+#[derive(Debug, Clone)]
+pub struct EmaState {
+    period: usize,
+    k: f64,
+    count: usize,
+    seed_sum: f64,
+    value: f64,
+}
+
+impl EmaState {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            k: 2.0 / (period + 1) as f64,
+            count: 0,
+            seed_sum: 0.0,
+            value: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, price: f64) -> f64 {
+        if self.period == 0 || self.count == 0 && price.is_nan() {
+            return f64::NAN;
+        }
+        self.count += 1;
+        if self.count <= self.period {
+            // seeded with the SMA of the first `period` prices
+            self.seed_sum += price;
+            if self.count < self.period {
+                return f64::NAN;
+            }
+            self.value = self.seed_sum / self.period as f64;
+            return self.value;
+        }
+        self.value = (price - self.value) * self.k + self.value;
+        self.value
+    }
+}
+
+/// Streaming `sma_slice`, leading NaNs are skipped and non-finite values in the window ignored.
+#[derive(Debug, Clone)]
+pub struct SmaState {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+    finite: usize,
+}
+
+impl SmaState {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            finite: 0,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> f64 {
+        if self.period == 0 || self.window.is_empty() && value.is_nan() {
+            return f64::NAN;
+        }
+        self.window.push_back(value);
+        if value.is_finite() {
+            self.sum += value;
+            self.finite += 1;
+        }
+        if self.window.len() > self.period {
+            let old = self.window.pop_front().unwrap();
+            if old.is_finite() {
+                self.sum -= old;
+                self.finite -= 1;
+            }
+        }
+
+        if self.window.len() < self.period || self.finite == 0 {
+            return f64::NAN;
+        }
+        self.sum / self.finite as f64
+    }
+}
+
+/// Streaming `macd_slice`, `next` returns `(macd, signal, histogram)`.
+#[derive(Debug, Clone)]
+pub struct MacdState {
+    fast: EmaState,
+    slow: EmaState,
+    signal: EmaState,
+    // prices to skip before the fast EMA starts
+    fast_delay: usize,
+}
+
+impl MacdState {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Self {
+            fast: EmaState::new(fast),
+            slow: EmaState::new(slow),
+            signal: EmaState::new(signal),
+            fast_delay: slow.saturating_sub(fast),
+        }
+    }
+
+    pub fn next(&mut self, price: f64) -> (f64, f64, f64) {
+        let fast = if self.fast_delay > 0 {
+            self.fast_delay -= 1;
+            f64::NAN
+        } else {
+            self.fast.next(price)
+        };
+        let macd = fast - self.slow.next(price);
+        let signal = self.signal.next(macd);
+        (macd, signal, macd - signal)
+    }
+}
+
+/// Streaming `stochastic_slice`, `next` returns `(k, d)`.
+///
+/// The window extremes are kept in monotonic queues, so each bar is amortized O(1).
+#[derive(Debug, Clone)]
+pub struct StochasticState {
+    period_k: usize,
+    index: usize,
+    // (index, value), highs decreasing and lows increasing from the front
+    highs: VecDeque<(usize, f64)>,
+    lows: VecDeque<(usize, f64)>,
+    smooth_k: SmaState,
+    d: SmaState,
+}
+
+impl StochasticState {
+    pub fn new(period_k: usize, smooth_k: usize, period_d: usize) -> Self {
+        Self {
+            period_k,
+            index: 0,
+            highs: VecDeque::with_capacity(period_k),
+            lows: VecDeque::with_capacity(period_k),
+            smooth_k: SmaState::new(smooth_k),
+            d: SmaState::new(period_d),
+        }
+    }
+
+    pub fn next(&mut self, close: f64, high: f64, low: f64) -> (f64, f64) {
+        if self.period_k == 0 {
+            return (f64::NAN, f64::NAN);
+        }
+        let i = self.index;
+        self.index += 1;
+
+        while self.highs.back().is_some_and(|&(_, h)| h <= high) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((i, high));
+        while self.lows.back().is_some_and(|&(_, l)| l >= low) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((i, low));
+        while self.highs.front().is_some_and(|&(j, _)| j + self.period_k <= i) {
+            self.highs.pop_front();
+        }
+        while self.lows.front().is_some_and(|&(j, _)| j + self.period_k <= i) {
+            self.lows.pop_front();
+        }
+
+        let raw_k = if i + 1 < self.period_k {
+            f64::NAN
+        } else {
+            let highest = self.highs.front().unwrap().1;
+            let lowest = self.lows.front().unwrap().1;
+            let denom = highest - lowest;
+            if denom == 0.0 { 0.0 } else { 100.0 * (close - lowest) / denom }
+        };
+
+        let k = self.smooth_k.next(raw_k);
+        let d = self.d.next(k);
+        (k, d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic wiggly prices with a trend, long enough to run past every warm-up
+    fn prices(n: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+        let close: Vec<f64> = (0..n).map(|i| 100.0 + i as f64 * 0.05 + (i as f64 * 0.37).sin() * 3.0).collect();
+        let high = close.iter().enumerate().map(|(i, c)| c + 0.5 + (i % 5) as f64 * 0.2).collect();
+        let low = close.iter().enumerate().map(|(i, c)| c - 0.5 - (i % 3) as f64 * 0.3).collect();
+        (close, high, low)
+    }
+
+    fn assert_series_eq(streamed: &[f64], batch: &[f64]) {
+        assert_eq!(streamed.len(), batch.len());
+        for (i, (s, b)) in streamed.iter().zip(batch).enumerate() {
+            assert!(s.is_nan() && b.is_nan() || (s - b).abs() < 1e-9, "index {}: streamed {} batch {}", i, s, b);
+        }
+    }
+
+    #[test]
+    fn streaming_ema_matches_batch() {
+        let (close, _, _) = prices(300);
+        for period in [1, 9, 20, 200] {
+            let mut state = EmaState::new(period);
+            let streamed: Vec<f64> = close.iter().map(|&p| state.next(p)).collect();
+            assert_series_eq(&streamed, &ema_slice(&close, period));
+        }
+    }
+
+    #[test]
+    fn streaming_sma_matches_batch() {
+        let (mut close, _, _) = prices(100);
+        close[3] = f64::NAN;
+        close[40] = f64::NAN;
+        for period in [1, 3, 14] {
+            let mut state = SmaState::new(period);
+            let streamed: Vec<f64> = close.iter().map(|&p| state.next(p)).collect();
+            assert_series_eq(&streamed, &sma_slice(&close, period));
+        }
+    }
+
+    #[test]
+    fn streaming_macd_matches_batch() {
+        let (close, _, _) = prices(300);
+        for (fast, slow, signal) in [(12, 26, 9), (3, 6, 4), (5, 5, 1)] {
+            let mut state = MacdState::new(fast, slow, signal);
+            let streamed: Vec<(f64, f64, f64)> = close.iter().map(|&p| state.next(p)).collect();
+
+            let (macd, signal, histogram) = macd_slice(&close, fast, slow, signal);
+            assert_series_eq(&streamed.iter().map(|s| s.0).collect::<Vec<_>>(), &macd);
+            assert_series_eq(&streamed.iter().map(|s| s.1).collect::<Vec<_>>(), &signal);
+            assert_series_eq(&streamed.iter().map(|s| s.2).collect::<Vec<_>>(), &histogram);
+        }
+    }
+
+    #[test]
+    fn streaming_stochastic_matches_batch() {
+        let (close, high, low) = prices(300);
+        for (period_k, smooth_k, period_d) in [(14, 1, 3), (14, 3, 3), (5, 2, 4)] {
+            let mut state = StochasticState::new(period_k, smooth_k, period_d);
+            let streamed: Vec<(f64, f64)> = (0..close.len()).map(|i| state.next(close[i], high[i], low[i])).collect();
+
+            let (k, d) = stochastic_slice(&close, &high, &low, period_k, smooth_k, period_d);
+            assert_series_eq(&streamed.iter().map(|s| s.0).collect::<Vec<_>>(), &k);
+            assert_series_eq(&streamed.iter().map(|s| s.1).collect::<Vec<_>>(), &d);
+        }
+    }
+
+    // 40 fixed bars: up, down, up again, with an unchanged close at bar 20.
+    // Reference values follow TA-Lib's definitions and were worked out separately from this module.
+    const OPEN: [f64; 40] = [
+        100.3, 100.83, 102.31, 103.28, 102.99, 102.62, 103.59, 105.5, 106.62, 106.38, 106.07, 106.95, 108.43, 109.03,
+        108.61, 108.68, 108.75, 109.15, 108.29, 106.32, 104.88, 104.68, 104.66, 103.52, 101.76, 100.93, 101.27,
+        101.29, 99.84, 99.1, 99.4, 100.83, 101.91, 101.8, 101.53, 102.44, 104.1, 104.87, 104.27, 103.73,
+    ];
+    const HIGH: [f64; 40] = [
+        101.0, 102.81, 104.13, 104.13, 103.39, 104.29, 106.14, 107.24, 107.02, 106.93, 107.6, 109.44, 109.71, 109.58,
+        109.52, 109.54, 109.31, 109.7, 108.99, 107.17, 105.28, 105.5, 105.36, 104.37, 102.16, 101.58, 101.97, 102.14,
+        100.24, 100.11, 101.82, 103.04, 102.33, 102.35, 102.9, 104.65, 105.05, 105.42, 104.97, 105.71,
+    ];
+    const LOW: [f64; 40] = [
+        100.0, 100.33, 101.61, 102.97, 102.4, 101.92, 103.29, 105.0, 105.38, 105.54, 105.57, 106.25, 108.13, 108.39,
+        107.91, 108.38, 108.25, 107.29, 105.8, 104.34, 104.14, 104.38, 103.3, 101.2, 100.56, 100.43, 100.29, 99.32,
+        98.56, 98.4, 99.1, 100.33, 101.21, 101.16, 101.03, 101.74, 103.8, 103.74, 103.2, 103.43,
+    ];
+    const CLOSE: [f64; 40] = [
+        100.6, 102.26, 103.43, 103.27, 102.9, 103.74, 105.44, 106.39, 106.08, 105.84, 106.9, 108.59, 109.31, 108.89,
+        108.82, 108.69, 108.91, 107.99, 106.1, 104.84, 104.84, 104.95, 103.8, 101.9, 100.86, 101.03, 100.99, 99.62,
+        99.06, 99.56, 101.12, 102.19, 101.93, 101.46, 102.2, 103.8, 104.65, 104.24, 103.9, 104.86,
+    ];
+    const VOLUME: [f64; 40] = [
+        1000.0, 1137.0, 1274.0, 1411.0, 1048.0, 1185.0, 1322.0, 1459.0, 1096.0, 1233.0, 1370.0, 1007.0, 1144.0,
+        1281.0, 1418.0, 1055.0, 1192.0, 1329.0, 1466.0, 1103.0, 1240.0, 1377.0, 1014.0, 1151.0, 1288.0, 1425.0,
+        1062.0, 1199.0, 1336.0, 1473.0, 1110.0, 1247.0, 1384.0, 1021.0, 1158.0, 1295.0, 1432.0, 1069.0, 1206.0,
+        1343.0,
+    ];
+
+    // NaN before `first`, then the listed `(index, value)` pairs
+    fn assert_reference(name: &str, series: &[f64], first: usize, expected: &[(usize, f64)]) {
+        assert_eq!(series.len(), CLOSE.len(), "{}", name);
+        assert!(series[..first].iter().all(|v| v.is_nan()), "{} has values before bar {}: {:?}", name, first, series);
+        assert!(series[first..].iter().all(|v| v.is_finite()), "{} has gaps after bar {}: {:?}", name, first, series);
+        for &(i, value) in expected {
+            assert!((series[i] - value).abs() < 1e-8, "{}[{}] = {}, expected {}", name, i, series[i], value);
+        }
+    }
+
+    #[test]
+    fn moving_averages_match_reference() {
+        assert_reference("sma 5", &sma_slice(&CLOSE, 5), 4, &[(4, 102.492), (20, 106.536), (39, 104.29)]);
+        assert_reference("ema 10", &ema_slice(&CLOSE, 10), 9, &[(9, 103.995), (10, 104.5231818182), (39, 103.3800135915)]);
+
+        let (macd, signal, histogram) = macd_slice(&CLOSE, 12, 26, 9);
+        assert_reference("macd", &macd, 25, &[(25, -0.0175), (33, -1.1092949519), (39, -0.1435309785)]);
+        assert_reference("macd signal", &signal, 33, &[(33, -0.8997221033), (39, -0.5580213299)]);
+        assert_reference("macd histogram", &histogram, 33, &[(33, -0.2095728486), (39, 0.4144903514)]);
+
+        let (macd, signal, histogram) = macd_slice(&CLOSE, 3, 6, 4);
+        assert_reference("macd 3/6/4", &macd, 5, &[(5, 0.6033333333), (8, 0.9122242468), (39, 0.5361173632)]);
+        assert_reference("macd 3/6/4 signal", &signal, 8, &[(8, 0.8679327624), (39, 0.5409420112)]);
+        assert_reference("macd 3/6/4 histogram", &histogram, 8, &[(39, -0.004824648)]);
+    }
+
+    #[test]
+    fn short_series_are_all_warm_up() {
+        assert!(ema_slice(&CLOSE[..5], 10).iter().all(|v| v.is_nan()));
+        assert!(ema_slice(&[], 10).is_empty());
+        let (macd, signal, _) = macd_slice(&CLOSE[..30], 12, 26, 9);
+        assert!(macd[25..].iter().all(|v| v.is_finite()));
+        assert!(signal.iter().all(|v| v.is_nan()));
+        assert!(rsi_slice(&CLOSE[..14], 14).iter().all(|v| v.is_nan()));
+        assert!(adx_slice(&HIGH[..9], &LOW[..9], &CLOSE[..9], 5).0.iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn oscillators_match_reference() {
+        assert_reference("rsi 14", &rsi_slice(&CLOSE, 14), 14, &[(14, 86.1795774648), (15, 85.1304347826), (39, 60.4943067534)]);
+
+        let (k, d) = stochastic_slice(&CLOSE, &HIGH, &LOW, 5, 3, 3);
+        assert_reference("stochastic k", &k, 6, &[(6, 80.2921633851), (39, 72.3620012661)]);
+        assert_reference("stochastic d", &d, 8, &[(8, 82.4803472786), (39, 76.5696547148)]);
+
+        assert_reference("cci 5", &cci_slice(&HIGH, &LOW, &CLOSE, 5), 4, &[(4, 38.6350817823), (39, 75.8988015979)]);
+        assert_reference("williams %r 5", &williams_r_slice(&HIGH, &LOW, &CLOSE, 5), 4, &[(4, -29.7820823245), (39, -21.4105793451)]);
+    }
+
+    #[test]
+    fn trend_and_volatility_match_reference() {
+        assert_reference("atr 14", &atr_slice(&HIGH, &LOW, &CLOSE, 14), 14, &[(14, 1.9457142857), (15, 1.8895918367), (39, 1.9298648829)]);
+
+        let (adx, plus_di, minus_di) = adx_slice(&HIGH, &LOW, &CLOSE, 5);
+        assert_reference("adx 5", &adx, 9, &[(9, 85.385306239), (10, 86.4724828472), (39, 51.3832076547)]);
+        assert_reference("+di 5", &plus_di, 5, &[(5, 42.0766378245), (39, 26.5318877308)]);
+        assert_reference("-di 5", &minus_di, 5, &[(5, 5.6365883807), (39, 7.80381079)]);
+
+        let (line, direction) = supertrend_slice(&HIGH, &LOW, &CLOSE, 5, 2.0);
+        assert_reference("supertrend", &line, 5, &[(5, 99.297), (20, 108.6668667065), (39, 100.8889013377)]);
+        // flips down at bar 19 and back up at bar 35
+        let flips: Vec<usize> = (6..40).filter(|&i| direction[i] != direction[i - 1]).collect();
+        assert_eq!((direction[5], flips), (1.0, vec![19, 35]));
+
+        let psar = psar_slice(&HIGH, &LOW, 0.02, 0.2);
+        assert_reference("psar", &psar, 1, &[(1, 100.0), (2, 100.0562), (17, 109.71), (20, 109.3784), (30, 98.4), (39, 100.9338335007)]);
+    }
+
+    #[test]
+    fn volume_and_channels_match_reference() {
+        assert_reference("vwap 5", &vwap_slice(&HIGH, &LOW, &CLOSE, &VOLUME, 5), 4, &[(4, 102.4509693356), (39, 104.2138728658)]);
+        assert_reference("obv", &obv_slice(&CLOSE, &VOLUME), 0, &[(0, 1000.0), (19, -350.0), (20, -350.0), (39, -220.0)]);
+
+        let (upper, middle, lower) = keltner_slice(&HIGH, &LOW, &CLOSE, 10, 5, 2.0);
+        assert_reference("keltner upper", &upper, 9, &[(9, 107.7926768), (39, 107.2207167353)]);
+        assert_reference("keltner middle", &middle, 9, &[(9, 103.995)]);
+        assert_reference("keltner lower", &lower, 9, &[(9, 100.1973232), (39, 99.5393104476)]);
+
+        let (upper, middle, lower) = donchian_slice(&HIGH, &LOW, 5);
+        assert_reference("donchian upper", &upper, 4, &[(4, 104.13), (39, 105.71)]);
+        assert_reference("donchian middle", &middle, 4, &[(4, 102.065), (39, 103.725)]);
+        assert_reference("donchian lower", &lower, 4, &[(4, 100.0), (39, 101.74)]);
+
+        let [conversion, base, span_a, span_b, lagging] = ichimoku_slice(&HIGH, &LOW, &CLOSE, 3, 5, 8, 4);
+        assert_reference("ichimoku conversion", &conversion, 2, &[(2, 102.065), (39, 104.455)]);
+        assert_reference("ichimoku base", &base, 4, &[(4, 102.065), (39, 103.725)]);
+        assert_reference("ichimoku span a", &span_a, 8, &[(8, 102.4675), (39, 102.665)]);
+        assert_reference("ichimoku span b", &span_b, 11, &[(11, 103.62), (39, 101.525)]);
+        assert_eq!((lagging[0], lagging[35]), (102.9, 104.86));
+        assert!(lagging[36..].iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn heikin_ashi_matches_reference() {
+        let (open, high, low, close) = heikin_ashi_slice(&OPEN, &HIGH, &LOW, &CLOSE);
+        assert_reference("ha open", &open, 0, &[(0, 100.45), (1, 100.4625), (39, 104.0388927812)]);
+        assert_reference("ha high", &high, 0, &[(39, 105.71)]);
+        assert_reference("ha low", &low, 0, &[(39, 103.43)]);
+        assert_reference("ha close", &close, 0, &[(0, 100.475), (39, 104.4325)]);
+    }
+}
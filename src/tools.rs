@@ -1,8 +1,9 @@
 use crate::calculator::{calculate_pnl, calculate_roe};
+use crate::config;
 use crate::enums::{OrderCommand, Timeframe};
 use crate::models::bot::Bot;
-use crate::models::models::{BotStatistic, Candle};
-use chrono::{DateTime, FixedOffset, NaiveDateTime, Timelike, Utc};
+use crate::models::models::{BotStatistic, Candle, PriceRange};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
 use log::debug;
 use std::cmp::Ordering;
 use std::time::Duration;
@@ -53,20 +54,35 @@ pub fn get_volume(candles: &[Candle]) -> Vec<f64> {
     vol
 }
 pub fn format_timeframe(timeframe: &Timeframe) -> String {
-    match timeframe {
-        Timeframe::Min1 => "1m".to_string(),
-        Timeframe::Min5 => "5m".to_string(),
-        Timeframe::Min15 => "15m".to_string(),
-        Timeframe::Min30 => "30m".to_string(),
-        Timeframe::Hour1 => "1h".to_string(),
-        Timeframe::Hour4 => "4h".to_string(),
-    }
+    timeframe.as_str().to_string()
 }
-pub fn should_close_position(price: f64, bot: &Bot) -> bool {
+/// Fill price if the stop-loss or take-profit was crossed anywhere inside `range`.
+///
+/// Fills happen at the level, or at the first price past it when the whole range gapped through,
+/// minus `slippage_pct` against the position. When both levels are inside the range the stop-loss
+/// is assumed to be hit first.
+pub fn get_exit_price(range: &PriceRange, bot: &Bot, slippage_pct: f64) -> Option<f64> {
+    let slippage = slippage_pct / 100.0;
     match bot.order_type {
-        OrderCommand::Long => price <= bot.order_stop_loss || price >= bot.order_take_profit,
-        OrderCommand::Short => price >= bot.order_stop_loss || price <= bot.order_take_profit,
-        _ => false,
+        OrderCommand::Long => {
+            if range.low <= bot.order_stop_loss {
+                Some(bot.order_stop_loss.min(range.high) * (1.0 - slippage))
+            } else if range.high >= bot.order_take_profit {
+                Some(bot.order_take_profit.max(range.low) * (1.0 - slippage))
+            } else {
+                None
+            }
+        }
+        OrderCommand::Short => {
+            if range.high >= bot.order_stop_loss {
+                Some(bot.order_stop_loss.max(range.low) * (1.0 + slippage))
+            } else if range.low <= bot.order_take_profit {
+                Some(bot.order_take_profit.min(range.high) * (1.0 + slippage))
+            } else {
+                None
+            }
+        }
+        _ => None,
     }
 }
 pub fn update_pnl_and_roe(bot: &mut Bot, price: f64) {
@@ -79,14 +95,27 @@ pub fn update_pnl_and_roe(bot: &mut Bot, price: f64) {
     bot.roe = calculate_roe(bot.order_entry_price, price, bot.leverage, &bot.order_type);
 }
 pub fn is_timeframe_now(bot: &Bot, now: &DateTime<FixedOffset>) -> bool {
-    match bot.timeframe {
-        Timeframe::Min1 => true,
-        Timeframe::Min5 => now.minute() % 5 == 0,
-        Timeframe::Min15 => now.minute() % 15 == 0,
-        Timeframe::Min30 => now.minute() % 30 == 0,
-        Timeframe::Hour1 => now.minute() % 60 == 0,
-        Timeframe::Hour4 => now.hour() % 4 == 0 && now.minute() % 60 == 0,
+    bot.timeframe.is_boundary(minute_start_ms(now))
+}
+
+/// UTC epoch millis of the minute `now` falls in, scans run a few seconds past the boundary.
+pub fn minute_start_ms(now: &DateTime<FixedOffset>) -> u64 {
+    let ms = now.timestamp_millis().max(0) as u64;
+    ms - ms % 60_000
+}
+
+/// Marks bars that haven't closed at `now_ms` as forming and checks the last closed one is the bar
+/// that closed at the last `timeframe` boundary, so strategies never act on stale data.
+pub fn closed_candles(mut candles: Vec<Candle>, timeframe: Timeframe, now_ms: u64) -> Result<Vec<Candle>, String> {
+    for c in candles.iter_mut() {
+        c.is_closed &= c.close_time < now_ms;
+    }
 
+    let expected = timeframe.open_time(now_ms) - timeframe.duration_ms();
+    match candles.iter().rfind(|c| c.is_closed) {
+        Some(last) if last.open_time == expected => Ok(candles),
+        Some(last) => Err(format!("last closed {} kline opened at {}, expected {}", timeframe, last.open_time, expected)),
+        None => Err(format!("no closed {} klines", timeframe)),
     }
 }
 pub fn shift_stop_loss(bot: &mut Bot) {
@@ -163,16 +192,21 @@ pub fn get_date(time_zone: i32) -> DateTime<FixedOffset> {
      Utc::now().with_timezone(&FixedOffset::east_opt(time_zone * 3600).unwrap())
 }
 
-pub fn parse_time(time_str: &str) -> DateTime<FixedOffset> {
+pub fn date_from_millis(ms: u64, time_zone: i32) -> DateTime<FixedOffset> {
+    DateTime::from_timestamp_millis(ms as i64)
+        .unwrap_or_default()
+        .with_timezone(&FixedOffset::east_opt(time_zone * 3600).unwrap())
+}
+
+pub fn parse_time(time_str: &str) -> Result<DateTime<FixedOffset>, String> {
     // Parse without timezone
     let naive = NaiveDateTime::parse_from_str(time_str, "%Y-%m-%dT%H:%M")
-      .expect("Failed to parse time");
+      .map_err(|_| format!("invalid time {}, expected YYYY-MM-DDTHH:MM", time_str))?;
 
-    // Istanbul is UTC+3
-    let ist_offset = FixedOffset::east_opt(3 * 3600).unwrap(); // 3 hours in seconds
+    let offset = FixedOffset::east_opt(config::get().time_zone * 3600).unwrap();
 
     // Convert to DateTime<FixedOffset>
-    DateTime::from_naive_utc_and_offset(naive, ist_offset)
+    Ok(DateTime::from_naive_utc_and_offset(naive, offset))
 }
 
 fn cmp_f64(a: &f64, b: &f64) -> Ordering {
@@ -184,3 +218,103 @@ fn cmp_f64(a: &f64, b: &f64) -> Ordering {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::Symbol;
+    use crate::models::models::StrategyContainer;
+
+    // 2025-01-06 00:00 UTC
+    const MIDNIGHT_MS: u64 = 1_736_121_600_000;
+    const HOUR_MS: u64 = 3_600_000;
+
+    fn candle(open_time: u64, timeframe: Timeframe) -> Candle {
+        Candle {
+            close: 1.0,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            open_time,
+            close_time: open_time + timeframe.duration_ms() - 1,
+            volume: 1.0,
+            is_closed: true,
+        }
+    }
+
+    #[test]
+    fn forming_candle_is_excluded() {
+        let tf = Timeframe::Hour4;
+        let now = MIDNIGHT_MS + 4 * HOUR_MS + 3_000;
+        let candles = vec![candle(MIDNIGHT_MS - 4 * HOUR_MS, tf), candle(MIDNIGHT_MS, tf), candle(MIDNIGHT_MS + 4 * HOUR_MS, tf)];
+
+        let candles = closed_candles(candles, tf, now).unwrap();
+        assert!(!candles[2].is_closed);
+
+        let mut sc = StrategyContainer::new();
+        sc.insert_candles(tf, Symbol::new("SOLUSDT"), candles);
+        let closed = sc.get_candles(&tf, &Symbol::new("SOLUSDT")).unwrap();
+        assert_eq!(closed.last().unwrap().open_time, MIDNIGHT_MS);
+        assert_eq!(closed.len(), 2);
+        assert_eq!(sc.get_live_bar(&tf, &Symbol::new("SOLUSDT")).unwrap().open_time, MIDNIGHT_MS + 4 * HOUR_MS);
+    }
+
+    #[test]
+    fn stale_series_is_rejected() {
+        let tf = Timeframe::Hour1;
+        let now = MIDNIGHT_MS + 2 * HOUR_MS + 3_000;
+        // the 01:00 bar hasn't been published yet
+        let candles = vec![candle(MIDNIGHT_MS - HOUR_MS, tf), candle(MIDNIGHT_MS, tf)];
+
+        assert!(closed_candles(candles, tf, now).is_err());
+        assert!(closed_candles(Vec::new(), tf, now).is_err());
+    }
+
+    fn in_position(order_type: OrderCommand, stop_loss: f64, take_profit: f64) -> Bot {
+        let mut bot = Bot::new(Timeframe::Min5, Symbol::new("SOLUSDT"), "EmaMacd".to_string(), 100.0, 10.0, 0.8, 0.4, 0.1);
+        bot.in_pos = true;
+        bot.order_type = order_type;
+        bot.order_stop_loss = stop_loss;
+        bot.order_take_profit = take_profit;
+        bot
+    }
+
+    fn range(low: f64, high: f64) -> PriceRange {
+        PriceRange { high, low, last: (high + low) / 2.0 }
+    }
+
+    #[test]
+    fn stop_loss_wins_when_both_levels_are_crossed() {
+        let long = in_position(OrderCommand::Long, 99.0, 102.0);
+        assert_eq!(get_exit_price(&range(98.5, 102.5), &long, 0.0), Some(99.0));
+        assert_eq!(get_exit_price(&range(99.5, 101.5), &long, 0.0), None);
+
+        let short = in_position(OrderCommand::Short, 101.0, 98.0);
+        assert_eq!(get_exit_price(&range(97.5, 101.5), &short, 0.0), Some(101.0));
+        assert_eq!(get_exit_price(&range(98.5, 100.5), &short, 0.0), None);
+
+        assert_eq!(get_exit_price(&range(0.0, 1000.0), &in_position(OrderCommand::Wait, 99.0, 102.0), 0.0), None);
+    }
+
+    #[test]
+    fn gaps_fill_at_the_first_price_past_the_level() {
+        let long = in_position(OrderCommand::Long, 99.0, 102.0);
+        assert_eq!(get_exit_price(&range(96.0, 97.0), &long, 0.0), Some(97.0));
+        assert_eq!(get_exit_price(&range(103.0, 104.0), &long, 0.0), Some(103.0));
+
+        let short = in_position(OrderCommand::Short, 101.0, 98.0);
+        assert_eq!(get_exit_price(&range(102.0, 103.0), &short, 0.0), Some(102.0));
+        assert_eq!(get_exit_price(&range(96.0, 97.0), &short, 0.0), Some(97.0));
+    }
+
+    #[test]
+    fn slippage_works_against_the_position() {
+        let long = in_position(OrderCommand::Long, 99.0, 102.0);
+        assert_eq!(get_exit_price(&range(98.0, 100.0), &long, 1.0), Some(99.0 * 0.99));
+        assert_eq!(get_exit_price(&range(100.0, 103.0), &long, 1.0), Some(102.0 * 0.99));
+
+        let short = in_position(OrderCommand::Short, 101.0, 98.0);
+        assert_eq!(get_exit_price(&range(100.0, 102.0), &short, 1.0), Some(101.0 * 1.01));
+        assert_eq!(get_exit_price(&range(97.0, 100.0), &short, 1.0), Some(98.0 * 1.01));
+    }
+}